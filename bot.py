@@ -1347,6 +1347,8 @@ class BotRuntime:
             "confidence": 0.0,
             "confirmed": False,
             "confirmation_count": 0,
+            "confirmation_evidence": 0.0,
+            "evidence_bars": 0,
             "changed_at": 0.0,
         }
         self._belief_state: bayesian_engine.BeliefState = bayesian_engine.BeliefState(enabled=False)
@@ -9196,7 +9198,12 @@ class BotRuntime:
             )
         )
 
-    def _hmm_runtime_config(self, *, min_train_samples: int | None = None) -> dict[str, Any]:
+    def _hmm_runtime_config(
+        self,
+        *,
+        min_train_samples: int | None = None,
+        training_candles: int | None = None,
+    ) -> dict[str, Any]:
         resolved_min_samples = max(
             50,
             int(
@@ -9205,6 +9212,14 @@ class BotRuntime:
                 else min_train_samples
             ),
         )
+        resolved_training_candles = max(
+            1,
+            int(
+                getattr(config, "HMM_TRAINING_CANDLES", 4000)
+                if training_candles is None
+                else training_candles
+            ),
+        )
         return {
             "HMM_N_STATES": max(2, int(getattr(config, "HMM_N_STATES", 3))),
             "HMM_N_ITER": max(10, int(getattr(config, "HMM_N_ITER", 100))),
@@ -9217,6 +9232,7 @@ class BotRuntime:
                 300.0, float(getattr(config, "HMM_RETRAIN_INTERVAL_SEC", 86400.0))
             ),
             "HMM_MIN_TRAIN_SAMPLES": resolved_min_samples,
+            "HMM_TRAINING_CANDLES": resolved_training_candles,
             "HMM_BIAS_GAIN": max(0.0, float(getattr(config, "HMM_BIAS_GAIN", 1.0))),
             "HMM_BLEND_WITH_TREND": max(
                 0.0, min(1.0, float(getattr(config, "HMM_BLEND_WITH_TREND", 0.5)))
@@ -9384,6 +9400,9 @@ class BotRuntime:
                                 1,
                                 int(getattr(config, "HMM_SECONDARY_MIN_TRAIN_SAMPLES", 200)),
                             ),
+                            training_candles=int(
+                                getattr(config, "HMM_SECONDARY_TRAINING_CANDLES", 1440)
+                            ),
                         )
                     )
                     self._hmm_state_secondary["available"] = True
@@ -9405,6 +9424,9 @@ class BotRuntime:
                                 1,
                                 int(getattr(config, "HMM_TERTIARY_MIN_TRAIN_SAMPLES", 150)),
                             ),
+                            training_candles=int(
+                                getattr(config, "HMM_TERTIARY_TRAINING_CANDLES", 500)
+                            ),
                         )
                     )
                     self._hmm_state_tertiary["available"] = True
@@ -9434,6 +9456,25 @@ class BotRuntime:
         self._hmm_consensus = self._compute_hmm_consensus()
         self._update_hmm_tertiary_transition(_now())
 
+    @staticmethod
+    def _prefix_hmm_snapshot(snap: dict[str, Any], name: str) -> dict[str, Any]:
+        """Re-key a detector snapshot from `_hmm_<key>` to `_hmm_<name>_<key>`."""
+        out: dict[str, Any] = {}
+        for key, value in snap.items():
+            if isinstance(key, str) and key.startswith("_hmm_"):
+                out[f"_hmm_{name}_{key[len('_hmm_'):]}"] = value
+        return out
+
+    @staticmethod
+    def _unprefix_hmm_snapshot(snapshot: dict[str, Any], name: str) -> dict[str, Any]:
+        """Inverse of _prefix_hmm_snapshot: pull one detector's keys back out."""
+        prefix = f"_hmm_{name}_"
+        return {
+            f"_hmm_{key[len(prefix):]}": value
+            for key, value in snapshot.items()
+            if isinstance(key, str) and key.startswith(prefix)
+        }
+
     def _snapshot_hmm_state(self) -> dict[str, Any]:
         if not self._hmm_module:
             return {}
@@ -9443,30 +9484,14 @@ class BotRuntime:
                 snap = self._hmm_module.serialize_for_snapshot(self._hmm_detector)
                 if isinstance(snap, dict):
                     out.update(dict(snap))
-            if self._hmm_detector_secondary and hasattr(self._hmm_module, "serialize_for_snapshot"):
-                sec_snap = self._hmm_module.serialize_for_snapshot(self._hmm_detector_secondary)
-                if isinstance(sec_snap, dict):
-                    out["_hmm_secondary_regime_state"] = dict(
-                        sec_snap.get("_hmm_regime_state", {}) or {}
-                    )
-                    out["_hmm_secondary_last_train_ts"] = float(
-                        sec_snap.get("_hmm_last_train_ts", 0.0) or 0.0
-                    )
-                    out["_hmm_secondary_trained"] = bool(
-                        sec_snap.get("_hmm_trained", False)
-                    )
-            if self._hmm_detector_tertiary and hasattr(self._hmm_module, "serialize_for_snapshot"):
-                ter_snap = self._hmm_module.serialize_for_snapshot(self._hmm_detector_tertiary)
-                if isinstance(ter_snap, dict):
-                    out["_hmm_tertiary_regime_state"] = dict(
-                        ter_snap.get("_hmm_regime_state", {}) or {}
-                    )
-                    out["_hmm_tertiary_last_train_ts"] = float(
-                        ter_snap.get("_hmm_last_train_ts", 0.0) or 0.0
-                    )
-                    out["_hmm_tertiary_trained"] = bool(
-                        ter_snap.get("_hmm_trained", False)
-                    )
+            for name, detector in (
+                ("secondary", self._hmm_detector_secondary),
+                ("tertiary", self._hmm_detector_tertiary),
+            ):
+                if detector and hasattr(self._hmm_module, "serialize_for_snapshot"):
+                    sub_snap = self._hmm_module.serialize_for_snapshot(detector)
+                    if isinstance(sub_snap, dict):
+                        out.update(self._prefix_hmm_snapshot(sub_snap, name))
         except Exception as e:
             logger.warning("HMM snapshot serialization failed: %s", e)
         return out
@@ -9479,28 +9504,17 @@ class BotRuntime:
         try:
             if self._hmm_detector and "_hmm_regime_state" in snapshot and hasattr(self._hmm_module, "restore_from_snapshot"):
                 self._hmm_module.restore_from_snapshot(self._hmm_detector, snapshot)
-            if (
-                self._hmm_detector_secondary
-                and "_hmm_secondary_regime_state" in snapshot
-                and hasattr(self._hmm_module, "restore_from_snapshot")
+            for name, detector in (
+                ("secondary", self._hmm_detector_secondary),
+                ("tertiary", self._hmm_detector_tertiary),
             ):
-                sec_snap = {
-                    "_hmm_regime_state": snapshot.get("_hmm_secondary_regime_state", {}),
-                    "_hmm_last_train_ts": snapshot.get("_hmm_secondary_last_train_ts", 0.0),
-                    "_hmm_trained": snapshot.get("_hmm_secondary_trained", False),
-                }
-                self._hmm_module.restore_from_snapshot(self._hmm_detector_secondary, sec_snap)
-            if (
-                self._hmm_detector_tertiary
-                and "_hmm_tertiary_regime_state" in snapshot
-                and hasattr(self._hmm_module, "restore_from_snapshot")
-            ):
-                ter_snap = {
-                    "_hmm_regime_state": snapshot.get("_hmm_tertiary_regime_state", {}),
-                    "_hmm_last_train_ts": snapshot.get("_hmm_tertiary_last_train_ts", 0.0),
-                    "_hmm_trained": snapshot.get("_hmm_tertiary_trained", False),
-                }
-                self._hmm_module.restore_from_snapshot(self._hmm_detector_tertiary, ter_snap)
+                if (
+                    detector
+                    and f"_hmm_{name}_regime_state" in snapshot
+                    and hasattr(self._hmm_module, "restore_from_snapshot")
+                ):
+                    sub_snap = self._unprefix_hmm_snapshot(snapshot, name)
+                    self._hmm_module.restore_from_snapshot(detector, sub_snap)
         except Exception as e:
             logger.warning("HMM snapshot restore failed: %s", e)
         finally:
@@ -9657,7 +9671,7 @@ class BotRuntime:
         try:
             closes_arr = self._hmm_numpy.asarray(closes, dtype=float)
             volumes_arr = self._hmm_numpy.asarray(volumes, dtype=float)
-            self._hmm_detector_secondary.update(closes_arr, volumes_arr)
+            self._hmm_detector_secondary.update(closes_arr, volumes_arr, as_of_ts=now)
             self._hmm_state_secondary["error"] = ""
         except Exception as e:
             logger.warning("Secondary HMM inference failed: %s", e)
@@ -9781,7 +9795,7 @@ class BotRuntime:
         try:
             closes_arr = self._hmm_numpy.asarray(closes, dtype=float)
             volumes_arr = self._hmm_numpy.asarray(volumes, dtype=float)
-            self._hmm_detector_tertiary.update(closes_arr, volumes_arr)
+            self._hmm_detector_tertiary.update(closes_arr, volumes_arr, as_of_ts=now)
             self._hmm_state_tertiary["error"] = ""
         except Exception as e:
             logger.warning("Tertiary HMM inference failed: %s", e)
@@ -10051,7 +10065,7 @@ class BotRuntime:
         try:
             closes_arr = self._hmm_numpy.asarray(closes, dtype=float)
             volumes_arr = self._hmm_numpy.asarray(volumes, dtype=float)
-            self._hmm_detector.update(closes_arr, volumes_arr)
+            self._hmm_detector.update(closes_arr, volumes_arr, as_of_ts=now)
             self._hmm_state["error"] = ""
         except Exception as e:
             logger.warning("HMM inference failed: %s", e)
@@ -10232,18 +10246,15 @@ class BotRuntime:
         return [0.0, 1.0, 0.0]
 
     @staticmethod
-    def _resample_candles_from_lower_interval(
+    def _contiguous_candle_runs(
         rows: list[dict[str, float | int | None]],
         *,
-        group_size: int,
         base_interval_sec: float,
-    ) -> list[dict[str, float | int | None]]:
-        if group_size <= 1 or not rows:
-            return []
-
+    ) -> list[list[dict[str, float | int | None]]]:
+        """Valid rows in time order, split wherever a base candle is missing."""
         step_sec = max(1.0, float(base_interval_sec))
-        out: list[dict[str, float | int | None]] = []
-        block: list[dict[str, float | int | None]] = []
+        runs: list[list[dict[str, float | int | None]]] = []
+        run: list[dict[str, float | int | None]] = []
         prev_ts = None
 
         for row in sorted(rows, key=lambda r: float(r.get("time", 0.0) or 0.0)):
@@ -10263,25 +10274,36 @@ class BotRuntime:
                 prev_ts is not None
                 and abs(ts - (float(prev_ts) + step_sec)) <= 1.0
             )
-            if block and not contiguous:
-                block = []
-            block.append(row)
+            if run and not contiguous:
+                runs.append(run)
+                run = []
+            run.append(row)
             prev_ts = ts
 
-            if len(block) < group_size:
-                continue
+        if run:
+            runs.append(run)
+        return runs
 
-            try:
+    @staticmethod
+    def _resample_candles_from_lower_interval(
+        rows: list[dict[str, float | int | None]],
+        *,
+        group_size: int,
+        base_interval_sec: float,
+    ) -> list[dict[str, float | int | None]]:
+        if group_size <= 1 or not rows:
+            return []
+
+        out: list[dict[str, float | int | None]] = []
+        runs = BotRuntime._contiguous_candle_runs(rows, base_interval_sec=base_interval_sec)
+        for run in runs:
+            for start in range(0, len(run) - group_size + 1, group_size):
+                block = run[start:start + group_size]
                 b_open = float(block[0].get("open", 0.0) or 0.0)
                 b_close = float(block[-1].get("close", 0.0) or 0.0)
                 b_high = max(float(x.get("high", 0.0) or 0.0) for x in block)
                 b_low = min(float(x.get("low", 0.0) or 0.0) for x in block)
                 b_volume = sum(float(x.get("volume", 0.0) or 0.0) for x in block)
-            except Exception:
-                block = []
-                continue
-
-            if min(b_open, b_high, b_low, b_close) > 0 and b_volume >= 0:
                 out.append(
                     {
                         "time": float(block[0].get("time", 0.0) or 0.0),
@@ -10293,7 +10315,6 @@ class BotRuntime:
                         "trade_count": None,
                     }
                 )
-            block = []
 
         return out
 
@@ -10314,14 +10335,29 @@ class BotRuntime:
         if not rows:
             return [], []
 
-        resampled = self._resample_candles_from_lower_interval(
-            rows,
-            group_size=group_size,
-            base_interval_sec=float(secondary_interval * 60),
-        )
-        if len(resampled) > int(target_candles):
-            resampled = resampled[-int(target_candles):]
-        return self._extract_close_volume(resampled)
+        base_interval_sec = float(secondary_interval * 60)
+        resample = getattr(self._hmm_module, "resample_closes_volumes", None)
+        if resample is None:
+            resampled = self._resample_candles_from_lower_interval(
+                rows,
+                group_size=group_size,
+                base_interval_sec=base_interval_sec,
+            )
+            if len(resampled) > int(target_candles):
+                resampled = resampled[-int(target_candles):]
+            return self._extract_close_volume(resampled)
+
+        # Same close/volume aggregation the detectors' own resampler applies,
+        # run per gap-free stretch so no bucket spans a missing candle.
+        closes: list[float] = []
+        volumes: list[float] = []
+        for run in self._contiguous_candle_runs(rows, base_interval_sec=base_interval_sec):
+            run_closes, run_volumes = self._extract_close_volume(run)
+            c, v = resample(run_closes, run_volumes, group_size)
+            closes.extend(float(x) for x in c)
+            volumes.extend(float(x) for x in v)
+        target = int(target_candles)
+        return closes[-target:], volumes[-target:]
 
     def _update_hmm_tertiary_transition(self, now: float) -> None:
         state = dict(self._hmm_state_tertiary or {})
@@ -10336,6 +10372,10 @@ class BotRuntime:
         to_regime = str(transition.get("to_regime", regime) or regime).upper()
         changed_at = float(transition.get("changed_at", 0.0) or 0.0)
         confirmation_count = int(transition.get("confirmation_count", 0) or 0)
+        # Confidence-weighted evidence: each tertiary bar since the change
+        # contributes its confidence once (evidence_bars tracks bars counted).
+        evidence = float(transition.get("confirmation_evidence", 0.0) or 0.0)
+        evidence_bars = int(transition.get("evidence_bars", 0) or 0)
 
         if not is_ready:
             self._hmm_tertiary_transition = {
@@ -10345,6 +10385,8 @@ class BotRuntime:
                 "confidence": confidence,
                 "confirmed": False,
                 "confirmation_count": 0,
+                "confirmation_evidence": 0.0,
+                "evidence_bars": 0,
                 "changed_at": 0.0,
             }
             return
@@ -10354,12 +10396,16 @@ class BotRuntime:
             from_regime = regime
             to_regime = regime
             confirmation_count = 1
+            evidence = confidence
+            evidence_bars = 0
 
         if regime != to_regime:
             from_regime = to_regime
             to_regime = regime
             changed_at = float(state.get("last_update_ts", 0.0) or now)
             confirmation_count = 1
+            evidence = confidence
+            evidence_bars = 0
         else:
             interval_sec = max(
                 60.0,
@@ -10367,10 +10413,17 @@ class BotRuntime:
             )
             age_sec = max(0.0, float(now) - changed_at)
             confirmation_count = max(1, int(age_sec // interval_sec) + 1)
+            if confirmation_count - 1 > evidence_bars:
+                evidence += confidence * float(confirmation_count - 1 - evidence_bars)
+                evidence_bars = confirmation_count - 1
 
         transition_age_sec = max(0.0, float(now) - changed_at)
-        confirm_needed = max(1, int(getattr(config, "ACCUM_CONFIRMATION_CANDLES", 2)))
-        confirmed = bool(from_regime != to_regime and confirmation_count >= confirm_needed)
+        if bool(getattr(config, "ACCUM_CONFIRMATION_CONFIDENCE_WEIGHTED", False)):
+            evidence_needed = max(0.0, float(getattr(config, "ACCUM_CONFIRMATION_EVIDENCE", 1.0)))
+            confirmed = bool(from_regime != to_regime and evidence >= evidence_needed)
+        else:
+            confirm_needed = max(1, int(getattr(config, "ACCUM_CONFIRMATION_CANDLES", 2)))
+            confirmed = bool(from_regime != to_regime and confirmation_count >= confirm_needed)
         self._hmm_tertiary_transition = {
             "from_regime": from_regime,
             "to_regime": to_regime,
@@ -10378,6 +10431,8 @@ class BotRuntime:
             "confidence": confidence,
             "confirmed": confirmed,
             "confirmation_count": int(confirmation_count),
+            "confirmation_evidence": float(evidence),
+            "evidence_bars": int(evidence_bars),
             "changed_at": changed_at,
         }
 
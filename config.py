@@ -373,6 +373,10 @@ ACCUM_INTERVAL_SEC: float = _env("ACCUM_INTERVAL_SEC", 120.0, float)
 ACCUM_MAX_DRAWDOWN_PCT: float = _env("ACCUM_MAX_DRAWDOWN_PCT", 3.0, float)
 ACCUM_COOLDOWN_SEC: float = _env("ACCUM_COOLDOWN_SEC", 3600.0, float)
 ACCUM_CONFIRMATION_CANDLES: int = _env("ACCUM_CONFIRMATION_CANDLES", 2, int)
+# Confidence-weighted confirmation: each tertiary bar adds its confidence as
+# evidence and the transition confirms once evidence reaches the threshold.
+ACCUM_CONFIRMATION_CONFIDENCE_WEIGHTED: bool = _env("ACCUM_CONFIRMATION_CONFIDENCE_WEIGHTED", False, bool)
+ACCUM_CONFIRMATION_EVIDENCE: float = _env("ACCUM_CONFIRMATION_EVIDENCE", 1.0, float)
 
 # Hour (UTC) to send daily P&L summary via Telegram.
 # 0 = midnight UTC.
@@ -19,10 +19,11 @@ Author: Robert / Claude sketch — 2026-02-14
 
 from __future__ import annotations
 
+import copy
 import json
 import logging
 import time
-from dataclasses import dataclass, field, asdict
+from dataclasses import dataclass, field, asdict, replace
 from enum import IntEnum
 from typing import Optional
 
@@ -35,6 +36,29 @@ except ImportError:
 
 logger = logging.getLogger("hmm_regime")
 
+# Reported dwell for an absorbing state (self-transition of exactly 1.0).
+DWELL_SENTINEL_BARS = 1e9
+
+
+class NonPositivePrice(ValueError):
+    """A close price is zero, negative or non-finite (bad feed data)."""
+
+    def __init__(self, index: int, value: float):
+        self.index = int(index)
+        self.value = float(value)
+        super().__init__(f"non-positive close price {self.value!r} at index {self.index}")
+
+
+class FeatureCountMismatch(ValueError):
+    """Observation rows are not as wide as the detector's feature vector."""
+
+    def __init__(self, expected: int, actual: int):
+        self.expected = int(expected)
+        self.actual = int(actual)
+        super().__init__(
+            f"feature count mismatch: expected {self.expected} columns, got {self.actual}"
+        )
+
 
 # ---------------------------------------------------------------------------
 # 1. Regime definitions
@@ -43,12 +67,41 @@ logger = logging.getLogger("hmm_regime")
 class Regime(IntEnum):
     """
     Three latent states. Labels are assigned post-training by inspecting
-    the learned emission means (see RegimeDetector._label_states).
+    the learned emission means (see RegimeDetector._derive_label_map).
     """
     BEARISH  = 0
     RANGING  = 1
     BULLISH  = 2
 
+    @classmethod
+    def all(cls) -> list["Regime"]:
+        """Every regime in index order (BEARISH, RANGING, BULLISH)."""
+        return list(cls)
+
+    @classmethod
+    def count(cls) -> int:
+        return len(cls)
+
+    def as_str(self) -> str:
+        return self.name
+
+    def opposite(self) -> "Regime":
+        """BEARISH <-> BULLISH; RANGING maps to itself."""
+        return Regime(len(Regime) - 1 - int(self))
+
+    def direction(self) -> int:
+        """-1 for BEARISH, 0 for RANGING, +1 for BULLISH."""
+        return int(self) - int(Regime.RANGING)
+
+    @classmethod
+    def from_str(cls, name: str) -> "Regime":
+        """Case-insensitive lookup by name; raises ValueError if unknown."""
+        key = str(name or "").strip().upper()
+        try:
+            return cls[key]
+        except KeyError:
+            raise ValueError(f"unknown regime {name!r}")
+
 
 # ---------------------------------------------------------------------------
 # 2. Observation feature extraction
@@ -73,6 +126,19 @@ class FeatureExtractor:
     All parameters use the same halflife conventions as §15 for consistency.
     """
 
+    FEATURE_NAMES = (
+        "macd_hist_slope",
+        "ema_spread_pct",
+        "rsi_zone",
+        "volume_ratio",
+    )
+    ENRICHED_FEATURE_NAMES = (
+        "fill_imbalance",
+        "spread_realization",
+        "fill_time_derivative",
+        "congestion_ratio",
+    )
+
     def __init__(
         self,
         fast_ema_periods: int = 9,
@@ -83,6 +149,13 @@ class FeatureExtractor:
         rsi_period: int = 14,
         volume_avg_period: int = 20,
         enriched_features_enabled: bool = False,
+        detrend_span: int = 0,
+        volume_baseline: str = "ema",
+        volume_transform: str = "raw",
+        price_cleaning: str = "ffill",
+        ema_spread_clamp: Optional[tuple[float, float]] = None,
+        volume_ratio_clamp: Optional[tuple[float, float]] = None,
+        volume_ratio_span: int = 1,
     ):
         self.fast_ema_periods = fast_ema_periods
         self.slow_ema_periods = slow_ema_periods
@@ -91,13 +164,247 @@ class FeatureExtractor:
         self.macd_signal = macd_signal
         self.rsi_period = rsi_period
         self.volume_avg_period = volume_avg_period
+        # EMA span applied to the per-bar volume_ratio (1 = no smoothing).
+        self._set_period("volume_ratio_span", volume_ratio_span)
         self.enriched_features_enabled = bool(enriched_features_enabled)
+        # Slow EMA baseline subtracted before momentum features (0 = off).
+        self.detrend_span = max(0, int(detrend_span))
+        self.set_volume_baseline(volume_baseline)
+        self.set_volume_transform(volume_transform)
+        self.set_price_cleaning(price_cleaning)
+        self.set_ema_spread_clamp(ema_spread_clamp)
+        self.set_volume_ratio_clamp(volume_ratio_clamp)
         # Runtime private features (cold-start neutral defaults).
         self._fill_imbalance = 0.0
         self._spread_realization = 1.0
         self._fill_time_derivative = 0.0
         self._congestion_ratio = 0.0
 
+    # --- Period setters -----------------------------------------------------
+    #
+    # Changing any period changes the meaning of the feature columns, so a
+    # model trained under the old spans is stale until the detector retrains.
+
+    def _set_period(self, name: str, value: int) -> None:
+        try:
+            period = int(value)
+        except (TypeError, ValueError):
+            raise ValueError(f"{name} must be an integer >= 1, got {value!r}")
+        if period < 1 or period != value:
+            raise ValueError(f"{name} must be an integer >= 1, got {value!r}")
+        setattr(self, name, period)
+
+    def set_fast_ema_periods(self, value: int) -> None:
+        self._set_period("fast_ema_periods", value)
+
+    def set_slow_ema_periods(self, value: int) -> None:
+        self._set_period("slow_ema_periods", value)
+
+    def set_macd_fast(self, value: int) -> None:
+        self._set_period("macd_fast", value)
+
+    def set_macd_slow(self, value: int) -> None:
+        self._set_period("macd_slow", value)
+
+    def set_macd_signal(self, value: int) -> None:
+        self._set_period("macd_signal", value)
+
+    def set_rsi_period(self, value: int) -> None:
+        self._set_period("rsi_period", value)
+
+    def set_volume_avg_period(self, value: int) -> None:
+        self._set_period("volume_avg_period", value)
+
+    def set_volume_ratio_span(self, value: int) -> None:
+        """Short EMA over volume_ratio so a one-candle spike can't swing the regime."""
+        self._set_period("volume_ratio_span", value)
+
+    def set_detrend_span(self, value: int) -> None:
+        """0 disables detrending; otherwise same rules as the other spans."""
+        if value == 0:
+            self.detrend_span = 0
+            return
+        self._set_period("detrend_span", value)
+
+    PERIOD_FIELDS = (
+        "fast_ema_periods",
+        "slow_ema_periods",
+        "macd_fast",
+        "macd_slow",
+        "macd_signal",
+        "rsi_period",
+        "volume_avg_period",
+        "volume_ratio_span",
+    )
+
+    def feature_names(self) -> list[str]:
+        """Column names of `extract` output, in order."""
+        names = list(self.FEATURE_NAMES)
+        if self.enriched_features_enabled:
+            names.extend(self.ENRICHED_FEATURE_NAMES)
+        return names
+
+    def warmup_required(self) -> int:
+        """
+        Candles of history the longest configured span needs before the
+        features settle (includes the detrend baseline when enabled).
+        """
+        spans = [int(getattr(self, name)) for name in self.PERIOD_FIELDS]
+        spans.append(int(self.detrend_span))
+        return max(spans)
+
+    def config(self) -> dict:
+        """Span/transform settings that define the feature columns."""
+        out = {name: int(getattr(self, name)) for name in self.PERIOD_FIELDS}
+        out["detrend_span"] = int(self.detrend_span)
+        out["volume_baseline"] = str(self.volume_baseline)
+        out["volume_transform"] = str(self.volume_transform)
+        out["enriched_features_enabled"] = bool(self.enriched_features_enabled)
+        out["ema_spread_clamp"] = self.ema_spread_clamp
+        out["volume_ratio_clamp"] = self.volume_ratio_clamp
+        return out
+
+    def apply_config(self, cfg: dict) -> None:
+        """Install settings produced by `config()`; unknown keys are ignored."""
+        for name in self.PERIOD_FIELDS:
+            if name in cfg:
+                self._set_period(name, cfg[name])
+        if "detrend_span" in cfg:
+            self.set_detrend_span(cfg["detrend_span"])
+        if "volume_baseline" in cfg:
+            self.set_volume_baseline(cfg["volume_baseline"])
+        if "volume_transform" in cfg:
+            self.set_volume_transform(cfg["volume_transform"])
+        if "enriched_features_enabled" in cfg:
+            self.enriched_features_enabled = bool(cfg["enriched_features_enabled"])
+        if "ema_spread_clamp" in cfg:
+            self.set_ema_spread_clamp(cfg["ema_spread_clamp"])
+        if "volume_ratio_clamp" in cfg:
+            self.set_volume_ratio_clamp(cfg["volume_ratio_clamp"])
+
+    VOLUME_BASELINES = ("ema", "median")
+
+    def set_volume_baseline(self, value: str) -> None:
+        """
+        "ema" (default) or "median": the rolling median over
+        volume_avg_period bars shrugs off one-bar spikes that an EMA
+        remembers for many bars.
+        """
+        mode = str(value or "").strip().lower()
+        if mode not in self.VOLUME_BASELINES:
+            raise ValueError(f"volume_baseline must be one of {self.VOLUME_BASELINES}, got {value!r}")
+        self.volume_baseline = mode
+
+    VOLUME_TRANSFORMS = ("raw", "log")
+
+    def set_volume_transform(self, value: str) -> None:
+        """
+        "raw" (default) or "log": volume_ratio is computed on ln(1 + volume),
+        which tames volume's heavy right tail for the Gaussian emissions.
+        """
+        mode = str(value or "").strip().lower()
+        if mode not in self.VOLUME_TRANSFORMS:
+            raise ValueError(f"volume_transform must be one of {self.VOLUME_TRANSFORMS}, got {value!r}")
+        self.volume_transform = mode
+
+    @staticmethod
+    def _clamp_bounds(name: str, value) -> Optional[list[float]]:
+        """None, or [low, high] with low < high (a scalar c means [-c, c])."""
+        if value is None:
+            return None
+        try:
+            if np.ndim(value) == 0:
+                low, high = -abs(float(value)), abs(float(value))
+            else:
+                low, high = (float(v) for v in value)
+        except (TypeError, ValueError):
+            raise ValueError(f"{name} must be None, a scalar or [low, high], got {value!r}")
+        if not (np.isfinite(low) and np.isfinite(high)) or low >= high:
+            raise ValueError(f"{name} needs finite low < high, got {value!r}")
+        return [low, high]
+
+    def set_ema_spread_clamp(self, bounds) -> None:
+        """Clip ema_spread_pct to [low, high] (None = unbounded, the default)."""
+        self.ema_spread_clamp = self._clamp_bounds("ema_spread_clamp", bounds)
+
+    def set_volume_ratio_clamp(self, bounds) -> None:
+        """Clip volume_ratio to [low, high] (None = unbounded, the default)."""
+        self.volume_ratio_clamp = self._clamp_bounds("volume_ratio_clamp", bounds)
+
+    PRICE_CLEANING_POLICIES = ("error", "ffill")
+
+    def set_price_cleaning(self, value: str) -> None:
+        """
+        How extract() treats non-positive / non-finite closes: "error" raises
+        NonPositivePrice at the first offending index, "ffill" (default)
+        carries the last valid price forward.
+        """
+        mode = str(value or "").strip().lower()
+        if mode not in self.PRICE_CLEANING_POLICIES:
+            raise ValueError(
+                f"price_cleaning must be one of {self.PRICE_CLEANING_POLICIES}, got {value!r}"
+            )
+        self.price_cleaning = mode
+
+    def clean_closes(self, closes: np.ndarray) -> np.ndarray:
+        """Apply the price_cleaning policy; returns a float copy of `closes`."""
+        closes = np.array(closes, dtype=float)
+        bad = ~np.isfinite(closes) | (closes <= 0.0)
+        if not bad.any():
+            return closes
+        first_bad = int(np.argmax(bad))
+        if self.price_cleaning == "error":
+            raise NonPositivePrice(first_bad, closes[first_bad])
+        good = np.flatnonzero(~bad)
+        if good.size == 0:
+            raise NonPositivePrice(first_bad, closes[first_bad])
+        # Index of the last valid price at or before each bar; leading bad
+        # bars take the first valid price.
+        last_good = np.maximum.accumulate(np.where(bad, -1, np.arange(len(closes))))
+        last_good = np.where(last_good < 0, good[0], last_good)
+        logger.warning(
+            "HMM features: forward-filled %d non-positive close(s), first at index %d",
+            int(bad.sum()), first_bad,
+        )
+        return closes[last_good]
+
+    def validate(self, closes, volumes) -> dict:
+        """
+        Pre-flight data-quality report for a candle series; mutates nothing.
+
+        {
+            "length": int,                 # number of closes
+            "length_mismatch": bool,       # closes and volumes differ in length
+            "non_finite_closes": int,
+            "non_finite_volumes": int,
+            "non_positive_prices": int,    # finite closes <= 0
+            "warmup_required": int,
+            "enough_history": bool,        # length > warmup_required()
+            "ok": bool,                    # all of the above clean
+        }
+        """
+        c = np.asarray(closes, dtype=float).reshape(-1)
+        v = np.asarray(volumes, dtype=float).reshape(-1)
+        finite = np.isfinite(c)
+        warmup = self.warmup_required()
+        report = {
+            "length": int(len(c)),
+            "length_mismatch": bool(len(c) != len(v)),
+            "non_finite_closes": int((~finite).sum()),
+            "non_finite_volumes": int((~np.isfinite(v)).sum()),
+            "non_positive_prices": int((c[finite] <= 0.0).sum()),
+            "warmup_required": int(warmup),
+            "enough_history": bool(len(c) > warmup),
+        }
+        report["ok"] = bool(
+            not report["length_mismatch"]
+            and report["non_finite_closes"] == 0
+            and report["non_finite_volumes"] == 0
+            and report["non_positive_prices"] == 0
+            and report["enough_history"]
+        )
+        return report
+
     def set_private_features(self, metrics: dict | None) -> None:
         metrics = metrics or {}
         try:
@@ -131,6 +438,15 @@ class FeatureExtractor:
             out[i] = alpha * series[i] + (1 - alpha) * out[i - 1]
         return out
 
+    @staticmethod
+    def _rolling_median(series: np.ndarray, window: int) -> np.ndarray:
+        """Trailing median over `window` bars (expanding until full)."""
+        window = max(1, int(window))
+        out = np.empty(len(series))
+        for i in range(len(series)):
+            out[i] = np.median(series[max(0, i - window + 1):i + 1])
+        return out
+
     @staticmethod
     def _rsi(closes: np.ndarray, period: int) -> np.ndarray:
         """Standard RSI calculation."""
@@ -156,6 +472,26 @@ class FeatureExtractor:
         # prepend NaN for the first element (lost to diff)
         return np.concatenate([[np.nan], rsi])
 
+    # Extra history, in multiples of the longest span, that extract_tail
+    # feeds ahead of the requested rows. The recursive filters forget their
+    # seed geometrically, so 10 spans leaves a start-up error below ~e^-18.
+    TAIL_WARMUP_SPANS = 10
+
+    def extract_tail(self, closes: np.ndarray, volumes: np.ndarray, tail_len: int) -> np.ndarray:
+        """
+        Last `tail_len` rows of `extract(closes, volumes)`, computed over only
+        the suffix needed for them (plus TAIL_WARMUP_SPANS spans of warmup),
+        so live updates don't re-filter the whole history every bar.
+        """
+        tail_len = int(tail_len)
+        if tail_len <= 0:
+            return np.empty((0, len(self.feature_names())))
+        suffix = tail_len + self.rsi_period + self.TAIL_WARMUP_SPANS * self.warmup_required()
+        if len(closes) > suffix:
+            closes = closes[-suffix:]
+            volumes = volumes[-suffix:]
+        return self.extract(closes, volumes)[-tail_len:]
+
     def extract(self, closes: np.ndarray, volumes: np.ndarray) -> np.ndarray:
         """
         Extract observation matrix from OHLCV arrays.
@@ -169,31 +505,66 @@ class FeatureExtractor:
                           rsi_zone, volume_ratio]
             Only rows where all indicators are valid (no NaN) are returned.
         """
+        return self.extract_with_index(closes, volumes)[0]
+
+    def extract_with_index(
+        self, closes: np.ndarray, volumes: np.ndarray
+    ) -> tuple[np.ndarray, np.ndarray]:
+        """
+        `extract` plus the input candle index each kept row came from, so
+        callers can attribute rows to candles across warmup and any
+        non-finite rows dropped mid-series.
+        """
+        closes = self.clean_closes(closes)
+        volumes = np.asarray(volumes, dtype=float)
         n = len(closes)
         assert len(volumes) == n, "closes and volumes must be same length"
 
+        # --- Optional detrending (high-pass) ---
+        # Momentum features then describe deviations from the local trend
+        # instead of its absolute direction, which saturates on strong trends.
+        # The EMA spread is still expressed as a fraction of the price level.
+        if self.detrend_span > 0:
+            level = self._ema(closes, self.detrend_span)
+            momentum = closes - level
+        else:
+            momentum = closes
+            level = None
+
         # --- EMA spread (matches §15 trend_score) ---
-        fast_ema = self._ema(closes, self.fast_ema_periods)
-        slow_ema = self._ema(closes, self.slow_ema_periods)
+        fast_ema = self._ema(momentum, self.fast_ema_periods)
+        slow_ema = self._ema(momentum, self.slow_ema_periods)
+        spread_base = slow_ema if level is None else level
         ema_spread_pct = (fast_ema - slow_ema) / np.where(
-            slow_ema == 0, 1e-10, slow_ema
+            spread_base == 0, 1e-10, spread_base
         )
+        if self.ema_spread_clamp is not None:
+            ema_spread_pct = np.clip(ema_spread_pct, *self.ema_spread_clamp)
 
         # --- MACD histogram slope ---
-        macd_fast_ema = self._ema(closes, self.macd_fast)
-        macd_slow_ema = self._ema(closes, self.macd_slow)
+        macd_fast_ema = self._ema(momentum, self.macd_fast)
+        macd_slow_ema = self._ema(momentum, self.macd_slow)
         macd_line = macd_fast_ema - macd_slow_ema
         macd_signal = self._ema(macd_line, self.macd_signal)
         macd_hist = macd_line - macd_signal
         macd_hist_slope = np.concatenate([[0.0], np.diff(macd_hist)])
 
         # --- RSI zone: map 0-100 to -1..+1 ---
-        rsi_raw = self._rsi(closes, self.rsi_period)
+        rsi_raw = self._rsi(momentum, self.rsi_period)
         rsi_zone = (rsi_raw - 50.0) / 50.0  # -1 = oversold, +1 = overbought
 
         # --- Volume ratio ---
-        vol_avg = self._ema(volumes, self.volume_avg_period)
+        if self.volume_transform == "log":
+            volumes = np.log1p(np.clip(volumes, 0.0, None))
+        if self.volume_baseline == "median":
+            vol_avg = self._rolling_median(volumes, self.volume_avg_period)
+        else:
+            vol_avg = self._ema(volumes, self.volume_avg_period)
         volume_ratio = volumes / np.where(vol_avg == 0, 1e-10, vol_avg)
+        if self.volume_ratio_span > 1:
+            volume_ratio = self._ema(volume_ratio, self.volume_ratio_span)
+        if self.volume_ratio_clamp is not None:
+            volume_ratio = np.clip(volume_ratio, *self.volume_ratio_clamp)
 
         # --- Stack and trim NaN rows ---
         obs = np.column_stack([
@@ -216,13 +587,213 @@ class FeatureExtractor:
             obs = np.concatenate([obs, enriched], axis=1)
 
         valid_mask = ~np.any(np.isnan(obs), axis=1)
-        return obs[valid_mask]
+        return obs[valid_mask], np.flatnonzero(valid_mask)
+
+
+PREV_REGIME_FEATURE = "prev_regime"
+
+
+def append_previous_regime(obs, regimes) -> np.ndarray:
+    """
+    Append a prev_regime column to the observation rows: row t carries the
+    direction of regimes[t - 1] (-1 bearish, 0 ranging, +1 bullish). Row 0
+    has no prior bar and gets the neutral 0.0, as does a None entry.
+    """
+    obs = np.asarray(obs, dtype=float)
+    if len(regimes) != len(obs):
+        raise ValueError(f"need one regime per row: {len(regimes)} regimes for {len(obs)} rows")
+    column = np.zeros(len(obs))
+    for t in range(1, len(obs)):
+        if regimes[t - 1] is not None:
+            column[t] = float(Regime(regimes[t - 1]).direction())
+    return np.column_stack([obs.reshape(len(obs), -1), column])
+
+
+class RunningStandardizer:
+    """
+    Online per-feature z-score (Welford's mean/variance), for sessions that
+    should adapt to slow drift instead of freezing the training window's
+    statistics. Feed it rows from `extract` / `extract_tail` as bars arrive.
+
+    Variance is the population variance, so after seeing a whole series the
+    statistics equal a batch `obs.mean(axis=0)` / `obs.std(axis=0)`; columns
+    with (near) zero spread keep a unit std, as in training.
+    """
+
+    def __init__(self, n_features: int, standardize: bool = True):
+        self.n_features = int(n_features)
+        self.standardize = bool(standardize)  # False: push() passes rows through
+        self.count = 0
+        self.mean = np.zeros(self.n_features)
+        self._m2 = np.zeros(self.n_features)
+
+    def update(self, row) -> None:
+        """Fold one observation row into the running statistics."""
+        x = np.asarray(row, dtype=float).reshape(-1)
+        if x.size != self.n_features:
+            raise FeatureCountMismatch(self.n_features, x.size)
+        if not np.isfinite(x).all():
+            return
+        self.count += 1
+        delta = x - self.mean
+        self.mean = self.mean + delta / self.count
+        self._m2 = self._m2 + delta * (x - self.mean)
+
+    def std(self) -> np.ndarray:
+        """Running population std per feature (1.0 where undefined or ~0)."""
+        if self.count < 2:
+            return np.ones(self.n_features)
+        out = np.sqrt(self._m2 / self.count)
+        return np.where(out <= 1e-12, 1.0, out)
+
+    def transform(self, rows) -> np.ndarray:
+        """Z-score rows with the current statistics (no update)."""
+        return (np.asarray(rows, dtype=float) - self.mean) / self.std()
+
+    def push(self, row) -> np.ndarray:
+        """Update with `row`, then emit it standardized (raw if standardize is off)."""
+        self.update(row)
+        x = np.asarray(row, dtype=float).reshape(-1)
+        return self.transform(x) if self.standardize else x
 
 
 # ---------------------------------------------------------------------------
 # 3. HMM training and inference
 # ---------------------------------------------------------------------------
 
+# hmmlearn owns fitting; these numpy routines re-run inference on the fitted
+# parameters for the paths hmmlearn does not expose (custom priors, etc.).
+
+def _model_covars(model) -> np.ndarray:
+    """Per-state covariances: (K, F) diagonal or (K, F, F) full."""
+    covars = np.asarray(model.covars_, dtype=float)
+    if covars.ndim == 3:
+        off_diag = covars * (1.0 - np.eye(covars.shape[1]))
+        if not np.any(off_diag):
+            return np.diagonal(covars, axis1=1, axis2=2).copy()
+    return covars
+
+
+def _huber_penalty(z: np.ndarray, delta: Optional[float]) -> np.ndarray:
+    """
+    Per-residual penalty in standard deviations: 0.5·z² (Gaussian), or with
+    `delta` set, quadratic inside |z| <= delta and linear beyond it (Huber).
+    """
+    if delta is None:
+        return 0.5 * z * z
+    a = np.abs(z)
+    d = float(delta)
+    return np.where(a <= d, 0.5 * z * z, d * a - 0.5 * d * d)
+
+
+def _state_log_densities(
+    obs: np.ndarray,
+    means: np.ndarray,
+    covars: np.ndarray,
+    huber_delta: Optional[float] = None,
+) -> np.ndarray:
+    """
+    Gaussian log-density of each row under each state, shape (T, K).
+
+    With `huber_delta` the squared term is replaced by a Huber penalty so far
+    outliers cost linearly rather than quadratically (per feature for
+    diagonal covariances, on the Mahalanobis radius for full ones).
+    """
+    obs = np.atleast_2d(np.asarray(obs, dtype=float))
+    means = np.asarray(means, dtype=float)
+    covars = np.asarray(covars, dtype=float)
+    n_features = obs.shape[1]
+    log_2pi = n_features * np.log(2.0 * np.pi)
+    if covars.ndim == 2:
+        var = np.maximum(covars, 1e-12)
+        z = (obs[:, None, :] - means[None, :, :]) / np.sqrt(var)[None, :, :]
+        penalty = np.sum(_huber_penalty(z, huber_delta), axis=2)
+        return -0.5 * (log_2pi + np.sum(np.log(var), axis=1)[None, :]) - penalty
+
+    out = np.empty((obs.shape[0], means.shape[0]))
+    for k in range(means.shape[0]):
+        _, logdet = np.linalg.slogdet(covars[k])
+        diff = obs - means[k]
+        maha = np.sum(diff * np.linalg.solve(covars[k], diff.T).T, axis=1)
+        radius = np.sqrt(np.maximum(maha, 0.0))
+        out[:, k] = -0.5 * (log_2pi + logdet) - _huber_penalty(radius, huber_delta)
+    return out
+
+
+def _bhattacharyya_coefficient(
+    mean_a: np.ndarray,
+    cov_a: np.ndarray,
+    mean_b: np.ndarray,
+    cov_b: np.ndarray,
+) -> float:
+    """
+    Overlap of two Gaussians in [0, 1] (1 = identical, 0 = disjoint).
+    Covariances may be diagonal vectors or full matrices.
+    """
+    mean_a = np.asarray(mean_a, dtype=float)
+    mean_b = np.asarray(mean_b, dtype=float)
+    cov_a = np.asarray(cov_a, dtype=float)
+    cov_b = np.asarray(cov_b, dtype=float)
+    if cov_a.ndim == 1:
+        cov_a = np.diag(np.maximum(cov_a, 1e-12))
+    if cov_b.ndim == 1:
+        cov_b = np.diag(np.maximum(cov_b, 1e-12))
+    cov = 0.5 * (cov_a + cov_b)
+    diff = mean_a - mean_b
+    _, logdet = np.linalg.slogdet(cov)
+    _, logdet_a = np.linalg.slogdet(cov_a)
+    _, logdet_b = np.linalg.slogdet(cov_b)
+    distance = (
+        0.125 * float(diff @ np.linalg.solve(cov, diff))
+        + 0.5 * (logdet - 0.5 * (logdet_a + logdet_b))
+    )
+    return max(0.0, min(1.0, float(np.exp(-distance))))
+
+
+def _forward_backward(
+    start_probs: np.ndarray,
+    transmat: np.ndarray,
+    log_b: np.ndarray,
+) -> tuple[float, np.ndarray, np.ndarray]:
+    """
+    Scaled forward-backward pass.
+
+    Returns (log_likelihood, filtered (T, K), smoothed (T, K)). Emission
+    log-densities are shifted per row before exponentiating; the shift is
+    added back into the log-likelihood.
+    """
+    start = np.asarray(start_probs, dtype=float)
+    a_mat = np.asarray(transmat, dtype=float)
+    log_b = np.asarray(log_b, dtype=float)
+    n_obs, n_states = log_b.shape
+    shift = log_b.max(axis=1, keepdims=True)
+    b = np.exp(log_b - shift)
+
+    alpha = np.empty((n_obs, n_states))
+    scale = np.empty(n_obs)
+    a = start * b[0]
+    for t in range(n_obs):
+        if t > 0:
+            a = (alpha[t - 1] @ a_mat) * b[t]
+        c = float(a.sum())
+        if c <= 0.0 or not np.isfinite(c):
+            a = np.full(n_states, 1.0 / n_states)
+            c = 1e-300
+        else:
+            a = a / c
+        alpha[t] = a
+        scale[t] = c
+
+    beta = np.ones((n_obs, n_states))
+    for t in range(n_obs - 2, -1, -1):
+        beta[t] = a_mat @ (b[t + 1] * beta[t + 1]) / scale[t + 1]
+
+    gamma = alpha * beta
+    gamma = gamma / np.maximum(gamma.sum(axis=1, keepdims=True), 1e-300)
+    log_likelihood = float(np.sum(np.log(scale)) + np.sum(shift))
+    return log_likelihood, alpha, gamma
+
+
 @dataclass
 class RegimeState:
     """Serializable state for persistence in bot_state snapshot."""
@@ -230,17 +801,172 @@ class RegimeState:
     probabilities: list[float] = field(
         default_factory=lambda: [0.0, 1.0, 0.0]  # default: 100% ranging
     )
-    confidence: float = 0.0       # max(probabilities) - second_max
+    confidence: float = 0.0       # per HMM_CONFIDENCE_METRIC (default: top-two margin)
+    peak_probability: float = 1.0 # max(probabilities)
     bias_signal: float = 0.0      # -1.0 (full bearish) to +1.0 (full bullish)
     last_update_ts: float = 0.0
     observation_count: int = 0    # how many obs in current inference window
+    quality_tier: str = "shallow"     # training depth tier of the emitting model
+    confidence_modifier: float = 0.70 # advisory multiplier for that tier
+    window_capped: bool = False       # inference window was cut to the training depth
+    neutralized: bool = False         # regime forced to RANGING below the confidence threshold
 
     def to_dict(self) -> dict:
         return asdict(self)
 
+    def is_normalized(self, tol: float = 1e-6) -> bool:
+        """True when probabilities are finite, non-negative and sum to 1 ± tol."""
+        try:
+            p = np.asarray(self.probabilities, dtype=float).reshape(-1)
+        except (TypeError, ValueError):
+            return False
+        if p.size == 0 or not np.isfinite(p).all() or (p < 0.0).any():
+            return False
+        return abs(float(p.sum()) - 1.0) <= float(tol)
+
+    def normalize(self) -> None:
+        """
+        Repair probabilities in place: non-finite/negative entries become 0,
+        then rescale to sum 1. An all-zero vector falls back to 100% RANGING.
+        """
+        try:
+            p = np.asarray(self.probabilities, dtype=float).reshape(-1)
+        except (TypeError, ValueError):
+            p = np.zeros(0)
+        p = np.where(np.isfinite(p), np.clip(p, 0.0, None), 0.0)
+        total = float(p.sum())
+        if p.size == 0 or total <= 1e-12:
+            self.probabilities = [0.0, 1.0, 0.0]
+            return
+        self.probabilities = (p / total).tolist()
+
+    def fuse(self, other: "RegimeState", self_weight: Optional[float] = None) -> "RegimeState":
+        """
+        Blend two states (e.g. primary + secondary timeframe) into one.
+
+        Probabilities are mixed with `self_weight` (clamped to [0, 1]) or, when
+        None, in proportion to each state's confidence. Regime, confidence
+        (top-two margin), peak and bias (P(bull) - P(bear), no gain or
+        threshold) are re-derived from the mix; the newer last_update_ts
+        wins and the remaining fields come from `self`.
+        """
+        if self_weight is None:
+            total = max(0.0, float(self.confidence)) + max(0.0, float(other.confidence))
+            w = 0.5 if total <= 1e-12 else max(0.0, float(self.confidence)) / total
+        else:
+            w = min(1.0, max(0.0, float(self_weight)))
+        a = np.asarray(self.probabilities, dtype=float).reshape(-1)
+        b = np.asarray(other.probabilities, dtype=float).reshape(-1)
+        if a.size != b.size:
+            raise ValueError(f"cannot fuse {a.size}- and {b.size}-state probabilities")
+        p = w * a + (1.0 - w) * b
+        p = p / max(float(p.sum()), 1e-300)
+        return replace(
+            self,
+            regime=Regime(int(np.argmax(p))),
+            probabilities=p.tolist(),
+            confidence=RegimeDetector.compute_confidence(p, "margin"),
+            peak_probability=float(p.max()),
+            bias_signal=float(p[Regime.BULLISH] - p[Regime.BEARISH]),
+            last_update_ts=max(float(self.last_update_ts), float(other.last_update_ts)),
+        )
+
+    LOGODDS_EPS = 1e-6
+
+    def bull_bear_logodds(self) -> float:
+        """
+        ln(P(bull) / P(bear)), both clipped to [LOGODDS_EPS, 1] so one-hot
+        posteriors stay finite (about ±13.8). 0 when the two are equal or
+        the probabilities are malformed.
+        """
+        try:
+            p = np.asarray(self.probabilities, dtype=float).reshape(-1)
+        except (TypeError, ValueError):
+            return 0.0
+        if p.size != len(Regime) or not np.isfinite(p).all():
+            return 0.0
+        bull = min(1.0, max(self.LOGODDS_EPS, float(p[Regime.BULLISH])))
+        bear = min(1.0, max(self.LOGODDS_EPS, float(p[Regime.BEARISH])))
+        return float(np.log(bull / bear))
+
+    def effective_states(self) -> float:
+        """
+        Perplexity of the probabilities, exp(entropy): 1.0 for a one-hot
+        vector up to n for uniform over n states. Any state count works;
+        malformed probabilities give 0.0.
+        """
+        try:
+            p = np.asarray(self.probabilities, dtype=float).reshape(-1)
+        except (TypeError, ValueError):
+            return 0.0
+        if p.size == 0 or not np.isfinite(p).all() or (p < 0.0).any() or p.sum() <= 0.0:
+            return 0.0
+        p = p / p.sum()
+        nz = p[p > 0.0]
+        return float(np.exp(-np.sum(nz * np.log(nz))))
+
+    def probabilities_dict(self) -> dict[str, float]:
+        """
+        {"BEARISH": p, "RANGING": p, "BULLISH": p}, so callers don't have to
+        remember the index order. Raises ValueError unless there is exactly
+        one probability per regime.
+        """
+        if len(self.probabilities) != len(Regime):
+            raise ValueError(
+                f"expected {len(Regime)} probabilities, got {len(self.probabilities)}"
+            )
+        return {r.as_str(): float(p) for r, p in zip(Regime.all(), self.probabilities)}
+
+    def runner_up(self) -> tuple[Regime, float]:
+        """
+        Second most likely regime and its probability. Ties break like
+        np.argmax (lower index first): for [0.4, 0.4, 0.2] BEARISH wins and
+        RANGING is the runner-up. Raises ValueError unless there is exactly
+        one probability per regime.
+        """
+        p = np.asarray(self.probabilities, dtype=float).reshape(-1)
+        if p.size != len(Regime):
+            raise ValueError(f"expected {len(Regime)} probabilities, got {p.size}")
+        second = int(np.argsort(-p, kind="stable")[1])
+        return Regime(second), float(p[second])
+
+    MISMATCH_POLICIES = ("repair", "error")
+
     @classmethod
-    def from_dict(cls, d: dict) -> "RegimeState":
-        return cls(**{k: v for k, v in d.items() if k in cls.__dataclass_fields__})
+    def from_dict(cls, d: dict, on_mismatch: str = "repair") -> "RegimeState":
+        """
+        Build from a snapshot dict. When `regime` is not the argmax of
+        `probabilities` (hand-edited / corrupted snapshot), "repair" re-derives
+        the regime from the probabilities and "error" raises ValueError. A
+        `neutralized` RANGING state is taken as is.
+        """
+        policy = str(on_mismatch or "").strip().lower()
+        if policy not in cls.MISMATCH_POLICIES:
+            raise ValueError(f"on_mismatch must be one of {cls.MISMATCH_POLICIES}, got {on_mismatch!r}")
+        state = cls(**{k: v for k, v in d.items() if k in cls.__dataclass_fields__})
+        if not state.is_normalized(tol=1e-3) or len(state.probabilities) != len(Regime):
+            return state
+        p = np.asarray(state.probabilities, dtype=float)
+        try:
+            regime = Regime(int(state.regime))
+        except (TypeError, ValueError):
+            regime = None
+        if regime is not None and p[int(regime)] >= p.max():
+            return state
+        if state.neutralized and regime == Regime.RANGING:
+            return state
+        derived = Regime(int(np.argmax(p)))
+        if policy == "error":
+            raise ValueError(
+                f"snapshot regime {state.regime!r} disagrees with probabilities "
+                f"{state.probabilities} (argmax {derived.name})"
+            )
+        logger.warning(
+            "HMM restore: regime %r disagrees with probabilities %s; using %s",
+            state.regime, state.probabilities, derived.name,
+        )
+        state.regime = derived
+        return state
 
 
 class RegimeDetector:
@@ -261,14 +987,66 @@ class RegimeDetector:
     DEFAULT_CONFIG = {
         "HMM_N_STATES": 3,
         "HMM_N_ITER": 100,
+        "HMM_MAX_ITER": None,                 # hard EM iteration ceiling (None = HMM_N_ITER)
+        "HMM_TOL": 1e-2,                      # EM stops once the log-likelihood gain drops below this
         "HMM_COVARIANCE_TYPE": "diag",       # "diag" is more stable than "full"
         "HMM_INFERENCE_WINDOW": 50,           # last N observations for inference
+        "HMM_ADAPTIVE_WINDOW": False,         # size the window from the favored regime's dwell
+        "HMM_ADAPTIVE_WINDOW_MIN": 20,        # adaptive window bounds, in bars
+        "HMM_ADAPTIVE_WINDOW_MAX": 200,
+        "HMM_SCALE_CONFIDENCE_BY_WINDOW": False,  # confidence *= available rows / requested window
+        "HMM_CAP_WINDOW_TO_TRAINING": False,  # never infer over more rows than training_depth
+        "HMM_BOUNDED_HISTORY": False,         # update() only looks at the last max_history() bars
+        "HMM_MAX_HISTORY": 0,                 # cap for bounded history (0 = window + tail warmup)
         "HMM_CONFIDENCE_THRESHOLD": 0.15,     # min confidence to emit non-zero bias
+        "HMM_NEUTRAL_BELOW_THRESHOLD": False, # below the threshold, also emit regime RANGING
+        "HMM_CONFIDENCE_METRIC": "margin",    # "margin" | "entropy" | "gini"
         "HMM_RETRAIN_INTERVAL_SEC": 86400.0,  # retrain daily
+        "HMM_RETRAIN_GRACE_SEC": 0.0,         # untrained: wait this long after construct/restore
         "HMM_MIN_TRAIN_SAMPLES": 500,         # ~42 hours of 5-min candles
-        "HMM_BIAS_GAIN": 1.0,                 # scales bias_signal magnitude
+        "HMM_TRAIN_SUBSAMPLE": 1,             # fit every k-th training row (depth still counts all)
+        "HMM_CANDIDATE_HOLDOUT": 200,         # evaluate_candidate: tail bars scored, not trained on
+        "HMM_TRAINING_CANDLES": 4000,         # target depth for the "full" quality tier
+        "HMM_RANDOM_SEED": 42,                # fixes Baum-Welch init for reproducible fits
+        "HMM_CAPTURE_TRACE": False,           # record per-iteration params (see last_fit_trace)
+        "HMM_TRACE_MAX_ITER": 200,            # cap on captured iterations (oldest dropped)
+        "HMM_TRANSITION_INERTIA": 0.0,        # retrain: weight kept on the previous/prior transmat
+        "HMM_TRANSITION_PRIOR": None,         # 3x3 Regime-order transmat used instead of the previous
+        "HMM_MIN_SAMPLES_PER_STATE": 0,       # min summed gamma per state (0 = off)
+        "HMM_CONSTANT_JITTER": 1e-3,          # seeded noise std on constant columns at fit (0 = off)
+        "HMM_BIAS_GAIN": 1.0,                 # scales bias_signal; or [bearish_gain, bullish_gain]
+        "HMM_OUTPUT_PRECISION": 4,            # decimals for confidence/bias (None or -1 = no rounding)
+        "HMM_BIAS_MODE": "difference",        # "difference" | "expected" | "logodds"
+        "HMM_ROBUST_EMISSION": False,         # Huber-penalized emissions at inference
+        "HMM_ROBUST_DELTA": 1.5,              # Huber knee, in standard deviations
+        "HMM_TEMPERATURE": 1.0,               # emission log-densities divided by T (>1 softens)
+        "HMM_EMISSION_FLOOR": 0.0,            # min emission likelihood relative to the row's best state (0 = off)
+        "HMM_EWMA_COVARIANCE": False,         # track emission covariances between retrains
+        "HMM_EWMA_DECAY": 0.02,               # per-bar EWMA weight (scaled by state posterior)
+        "HMM_ONLINE_TRANSITIONS": False,      # nudge transmat toward recent transitions between retrains
+        "HMM_ONLINE_TRANSITIONS_RATE": 0.01,  # per-update learning rate for HMM_ONLINE_TRANSITIONS
+        "HMM_HEALTH_MIN_TIER": "baseline",    # health(): shallowest acceptable training tier
+        "HMM_HEALTH_MIN_SEPARATION": 0.05,    # health(): min 1 - Bhattacharyya overlap per adjacent pair
+        "HMM_PREV_REGIME_FEATURE": False,     # append the previous bar's regime as a feature column
+        "HMM_MAX_ABS_BIAS": 1.0,              # risk-layer cap on |bias_signal| after gain/clamp
+        "HMM_HIT_RATE_WINDOW": 200,           # resolved calls kept for rolling_hit_rate()
+        "HMM_UPDATE_FAST_PATH": False,        # update() returns the cached state for an unchanged input tail
+        "HMM_MIN_DWELL_BARS": 1,              # updates a newly entered regime is held before it may change
+        "HMM_STALL_DECAY_UPDATES": 0,         # unchanged-tail updates until confidence hits 0 (0 = off)
+        "HMM_CUSUM_REFERENCE": 0.35,          # confidence level the drift CUSUM expects
+        "HMM_CUSUM_SLACK": 0.05,              # tolerated shortfall per update before accumulating
+        "HMM_CUSUM_THRESHOLD": 3.0,           # confidence_drift() above this suggests retraining
+        "HMM_CHANGE_POINT_WINDOW": 12,        # bars per sub-window for change_point_score
         "HMM_BLEND_WITH_TREND": 0.5,          # 0=pure HMM, 1=pure §15 trend_score
         "ENRICHED_FEATURES_ENABLED": False,
+        "DETREND_SPAN": 0,                    # slow EMA baseline for momentum features (0 = off)
+        "VOLUME_BASELINE": "ema",             # "ema" | "median" denominator for volume_ratio
+        "VOLUME_TRANSFORM": "raw",            # "raw" | "log" (ln(1 + volume)) before volume_ratio
+        "PRICE_CLEANING": "ffill",            # "ffill" | "error" for non-positive closes
+        "EMA_SPREAD_CLAMP": None,             # [low, high] (or c for ±c) bound on ema_spread_pct
+        "VOLUME_RATIO_CLAMP": None,           # [low, high] bound on volume_ratio
+        "VOLUME_RATIO_SPAN": 1,               # EMA span smoothing volume_ratio (1 = off)
+        "HMM_STATE_MISMATCH_POLICY": "repair",  # restore: "repair" | "error" on regime/probs disagreement
     }
 
     def __init__(self, config: Optional[dict] = None):
@@ -278,11 +1056,54 @@ class RegimeDetector:
             )
 
         self.cfg = {**self.DEFAULT_CONFIG, **(config or {})}
+        self._supplied_config_keys = list((config or {}).keys())  # for config_report()
         self.model: Optional[GaussianHMM] = None
         self.extractor = FeatureExtractor(
-            enriched_features_enabled=bool(self.cfg.get("ENRICHED_FEATURES_ENABLED", False))
+            enriched_features_enabled=bool(self.cfg.get("ENRICHED_FEATURES_ENABLED", False)),
+            detrend_span=int(self.cfg.get("DETREND_SPAN", 0) or 0),
+            volume_baseline=str(self.cfg.get("VOLUME_BASELINE", "ema") or "ema"),
+            volume_transform=str(self.cfg.get("VOLUME_TRANSFORM", "raw") or "raw"),
+            price_cleaning=str(self.cfg.get("PRICE_CLEANING", "ffill") or "ffill"),
+            ema_spread_clamp=self.cfg.get("EMA_SPREAD_CLAMP"),
+            volume_ratio_clamp=self.cfg.get("VOLUME_RATIO_CLAMP"),
+            volume_ratio_span=int(self.cfg.get("VOLUME_RATIO_SPAN", 1) or 1),
         )
+        self._confidence_metric(self.cfg.get("HMM_CONFIDENCE_METRIC", "margin"))
+        self._reset_live_state()
+        self.extractor_config_mismatch = False  # set by restore_from_snapshot
+        self.state_probability_mismatch = False  # set by restore_from_snapshot
+        self.n_states_mismatch = False           # set by restore_from_snapshot
+        self._state_label_map: dict[int, Regime] = {}
+        self._previous_label_map: dict[int, Regime] = {}
+        self._label_map_override: Optional[list[int]] = None
+        self._state_effective_counts: list[float] = []
+        self._ewma_covars: Optional[np.ndarray] = None    # HMM_EWMA_COVARIANCE estimate
+        self._ewma_last_row: Optional[np.ndarray] = None
+        self._fit_trace: Optional[list[dict]] = None  # HMM_CAPTURE_TRACE record of the last fit
+        self._fit_termination: Optional[dict] = None  # why the last EM run stopped
+        self._regime_labeler: Optional["RegimeDetector"] = None  # HMM_PREV_REGIME_FEATURE base model
+        self._candidate: Optional["RegimeDetector"] = None  # retrain awaiting commit_candidate
+        self.training_depth: int = 0          # candles supplied to the last successful train
+        self._last_train_ts: float = 0.0
+        self._grace_start_ts: float = time.time()  # construction / restore time
+        self._trained = False
+
+    def _reset_live_state(self) -> None:
+        """Reset per-session inference state; the trained model is untouched."""
         self.state = RegimeState()
+        self._regime_changed = False
+        self._filtering_disagreement = False
+        self._confidence_cusum = 0.0
+        self._last_row_norm: Optional[np.ndarray] = None       # for attribution()
+        self._last_raw_posterior: Optional[np.ndarray] = None
+        self._stalled_updates = 0
+        self._dwell_updates = 0  # updates the emitted regime has held (0 = age unknown)
+        self._window_capped = False
+        self._requested_window = 0
+        self._last_input_key: Optional[tuple] = None  # HMM_UPDATE_FAST_PATH
+        self._fast_path_hits = 0
+        self._hit_outcomes: list[bool] = []  # resolved directional calls, oldest first
+        self._pending_call: Optional[tuple[int, int, float]] = None  # (direction, len, close)
         # Keep the most recent raw observation row so downstream diagnostics can
         # read MACD/EMA/RSI/volume features without recomputing indicators.
         self.last_observation: Optional[IndicatorSnapshot] = None
@@ -290,9 +1111,147 @@ class RegimeDetector:
         self.last_ema_spread_pct: float = 0.0
         self.last_rsi_zone: float = 0.0
         self.last_volume_ratio: float = 0.0
-        self._state_label_map: dict[int, Regime] = {}
-        self._last_train_ts: float = 0.0
-        self._trained = False
+
+    def fork(self) -> "RegimeDetector":
+        """
+        Independent copy sharing the trained model but not the live state.
+
+        Config, extractor, model, label map and training metadata are deep
+        copied; the regime state starts neutral so the two detectors' live
+        trajectories diverge independently (e.g. for A/B runs).
+        """
+        clone = RegimeDetector.__new__(RegimeDetector)
+        clone.__dict__ = copy.deepcopy(self.__dict__)
+        clone._reset_live_state()
+        return clone
+
+    def replay(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        confidence_threshold: Optional[float] = None,
+        step: int = 1,
+        timestamps: Optional[list] = None,
+    ) -> list[tuple]:
+        """
+        Backtest helper: slide over the history and return the grid-bias
+        decision at every `step`-th bar from `min_history() - 1` on, as
+        (bar_index, compute_grid_bias dict) pairs — or (timestamp, dict),
+        with each update stamped at that timestamp, when `timestamps` is
+        given. Each entry equals `compute_grid_bias(update(closes[:i + 1],
+        volumes[:i + 1]), confidence_threshold)` on a fork, so the live
+        state is untouched. `confidence_threshold` defaults to
+        HMM_CONFIDENCE_THRESHOLD.
+        """
+        step = int(step)
+        if step < 1:
+            raise ValueError(f"step must be >= 1, got {step}")
+        n = len(closes)
+        if len(volumes) != n or (timestamps is not None and len(timestamps) != n):
+            raise ValueError("closes, volumes and timestamps must be the same length")
+        if confidence_threshold is None:
+            confidence_threshold = float(self.cfg["HMM_CONFIDENCE_THRESHOLD"])
+        sim = self.fork()
+        decisions = []
+        for i in range(max(0, self.min_history() - 1), n, step):
+            as_of = None if timestamps is None else timestamps[i]
+            state = sim.update(closes[:i + 1], volumes[:i + 1], as_of_ts=as_of)
+            key = i if timestamps is None else timestamps[i]
+            decisions.append((key, compute_grid_bias(state, confidence_threshold)))
+        return decisions
+
+    MIN_INFERENCE_WINDOW = 5  # same floor bot.py applies to HMM_INFERENCE_WINDOW
+
+    def set_inference_window(self, n: int) -> None:
+        """
+        Change HMM_INFERENCE_WINDOW in place; the trained model and state are
+        kept and the next update uses the new tail length.
+        """
+        try:
+            window = int(n)
+        except (TypeError, ValueError):
+            raise ValueError(
+                f"inference window must be an integer >= {self.MIN_INFERENCE_WINDOW}, got {n!r}"
+            )
+        if window != n or window < self.MIN_INFERENCE_WINDOW:
+            raise ValueError(
+                f"inference window must be an integer >= {self.MIN_INFERENCE_WINDOW}, got {n!r}"
+            )
+        self.cfg["HMM_INFERENCE_WINDOW"] = window
+
+    def min_history(self) -> int:
+        """
+        Minimum candles to pass to `update` so a full inference window of
+        settled features is available: extractor warmup + inference window
+        (the adaptive window's upper bound when HMM_ADAPTIVE_WINDOW is on).
+        """
+        if self.cfg.get("HMM_ADAPTIVE_WINDOW", False):
+            window = int(self.cfg.get("HMM_ADAPTIVE_WINDOW_MAX", 200))
+        else:
+            window = int(self.cfg["HMM_INFERENCE_WINDOW"])
+        return self.extractor.warmup_required() + window
+
+    def is_ready_for_inference(self, closes_len: int) -> bool:
+        """
+        True when `update` on `closes_len` candles will emit from the model:
+        trained, a fitted model actually present (with learned parameters
+        and standardization), and at least `min_history()` candles supplied.
+        """
+        if not self._trained or self.model is None:
+            return False
+        if any(
+            getattr(self.model, attr, None) is None
+            for attr in ("startprob_", "transmat_", "means_", "covars_")
+        ):
+            return False
+        if getattr(self, "_obs_mean", None) is None or getattr(self, "_obs_std", None) is None:
+            return False
+        return int(closes_len) >= self.min_history()
+
+    def effective_inference_window(self) -> int:
+        """
+        Rows the next update infers over. Fixed HMM_INFERENCE_WINDOW unless
+        HMM_ADAPTIVE_WINDOW is on, in which case it is the expected dwell of
+        the currently favored (last emitted) regime, clamped to
+        [HMM_ADAPTIVE_WINDOW_MIN, HMM_ADAPTIVE_WINDOW_MAX]: short in fast
+        regimes, long in persistent ones. With HMM_CAP_WINDOW_TO_TRAINING the
+        result never exceeds `training_depth` (the model never saw more).
+        """
+        window = self._configured_inference_window()
+        if self.cfg.get("HMM_CAP_WINDOW_TO_TRAINING", False) and self.training_depth > 0:
+            window = min(window, max(self.MIN_INFERENCE_WINDOW, int(self.training_depth)))
+        return window
+
+    def _configured_inference_window(self) -> int:
+        """Fixed or adaptive window, before any training-depth cap."""
+        fixed = int(self.cfg["HMM_INFERENCE_WINDOW"])
+        if not self.cfg.get("HMM_ADAPTIVE_WINDOW", False):
+            return fixed
+        dwell = self.expected_dwell_bars()
+        if not dwell:
+            return fixed
+        low = max(self.MIN_INFERENCE_WINDOW, int(self.cfg.get("HMM_ADAPTIVE_WINDOW_MIN", 20)))
+        high = max(low, int(self.cfg.get("HMM_ADAPTIVE_WINDOW_MAX", 200)))
+        favored = float(dwell[int(self.state.regime)])
+        return int(min(high, max(low, round(favored))))
+
+    def max_history(self) -> Optional[int]:
+        """
+        Bars the update paths process when HMM_BOUNDED_HISTORY is on: the
+        last HMM_MAX_HISTORY bars, never fewer than the inference window plus
+        the warmup `extract_tail` settles its filters over (0 = exactly that
+        floor), so trimming can't change the features. None when unbounded.
+        """
+        if not self.cfg.get("HMM_BOUNDED_HISTORY", False):
+            return None
+        cap = int(self.cfg.get("HMM_MAX_HISTORY", 0) or 0)
+        fx = self.extractor
+        floor = (
+            self.effective_inference_window()
+            + fx.rsi_period
+            + fx.TAIL_WARMUP_SPANS * fx.warmup_required()
+        )
+        return max(cap, floor)
 
     def set_private_features(self, metrics: dict | None) -> None:
         """
@@ -326,6 +1285,132 @@ class RegimeDetector:
             return 0.0
         return max(0.0, min(1.0, h / float(hmax)))
 
+    CONFIDENCE_METRICS = ("margin", "entropy", "gini")
+
+    @staticmethod
+    def _confidence_metric(metric) -> str:
+        """Normalized HMM_CONFIDENCE_METRIC name; ValueError when unknown."""
+        name = str(metric or "margin").strip().lower()
+        if name not in RegimeDetector.CONFIDENCE_METRICS:
+            raise ValueError(
+                f"HMM_CONFIDENCE_METRIC must be one of {RegimeDetector.CONFIDENCE_METRICS}, got {metric!r}"
+            )
+        return name
+
+    @staticmethod
+    def compute_confidence(
+        posterior: list[float] | tuple[float, ...] | np.ndarray,
+        metric: str = "margin",
+    ) -> float:
+        """
+        Confidence in [0, 1] for a regime posterior.
+
+        Metrics:
+            margin  -- top-two gap, max(p) - second_max(p)
+            entropy -- 1 - normalized Shannon entropy
+            gini    -- normalized purity (Σp² - 1/n) / (1 - 1/n);
+                       1.0 for a one-hot vector, 0.0 for uniform
+
+        Raises ValueError for any other metric name.
+        """
+        metric = RegimeDetector._confidence_metric(metric)
+        try:
+            p = np.asarray(posterior, dtype=float).reshape(-1)
+        except Exception:
+            return 0.0
+        if p.size == 0:
+            return 0.0
+        p = np.where(np.isfinite(p), p, 0.0)
+        p = np.clip(p, 0.0, 1.0)
+        total = float(p.sum())
+        if total <= 1e-12:
+            return 0.0
+        p = p / total
+
+        if metric == "entropy":
+            return 1.0 - RegimeDetector.compute_entropy(p)
+        if metric == "gini":
+            n = float(p.size)
+            if n <= 1.0:
+                return 1.0
+            purity = float(np.sum(p * p))
+            return max(0.0, min(1.0, (purity - 1.0 / n) / (1.0 - 1.0 / n)))
+        if p.size < 2:
+            return 1.0
+        top = np.sort(p)[::-1]
+        return max(0.0, min(1.0, float(top[0] - top[1])))
+
+    @staticmethod
+    def compute_bias(
+        probs: list[float] | tuple[float, ...] | np.ndarray,
+        mode: str = "difference",
+    ) -> float:
+        """
+        Directional bias in [-1, 1] from regime-ordered probabilities (before gain).
+
+        Modes:
+            difference -- p[BULLISH] - p[BEARISH]
+            expected   -- Σ d_i * p_i with directions d spaced evenly from -1
+                          (most bearish) to +1 (most bullish); equals
+                          "difference" for 3 states, extends to N ordered states
+            logodds    -- tanh(ln(p[BULLISH] / p[BEARISH]) / 2), i.e. direction
+                          conditional on being directional; RANGING mass ignored
+        """
+        p = np.asarray(probs, dtype=float).reshape(-1)
+        if p.size < 2:
+            return 0.0
+        p = np.where(np.isfinite(p), np.clip(p, 0.0, 1.0), 0.0)
+        mode = str(mode or "difference").strip().lower()
+        if mode == "expected":
+            directions = np.linspace(-1.0, 1.0, p.size)
+            total = float(p.sum())
+            if total <= 1e-12:
+                return 0.0
+            return max(-1.0, min(1.0, float(np.dot(directions, p)) / total))
+        if mode == "logodds":
+            eps = 1e-12
+            logit = float(np.log((p[-1] + eps) / (p[0] + eps)))
+            return float(np.tanh(0.5 * logit))
+        return max(-1.0, min(1.0, float(p[-1] - p[0])))
+
+    @staticmethod
+    def compute_quality_tier(
+        current_candles: int,
+        target_candles: int,
+        min_train_samples: int,
+    ) -> tuple[str, float]:
+        """
+        Training-depth tier and confidence modifier (same cut-offs as bot.py).
+
+        full >= target, deep >= 62.5% of target, baseline >= 25% of target,
+        otherwise shallow; the partial tiers never sit below min_train_samples.
+        """
+        current = max(0, int(current_candles))
+        target = max(1, int(target_candles))
+        min_train = max(1, int(min_train_samples))
+
+        if current >= target:
+            return "full", 1.00
+
+        baseline_threshold = max(min_train, int(round(target * 0.25)))
+        deep_threshold = max(min_train, int(round(target * 0.625)))
+        if deep_threshold <= baseline_threshold:
+            deep_threshold = baseline_threshold + 1
+
+        if current >= deep_threshold:
+            return "deep", 0.95
+        if current >= baseline_threshold:
+            return "baseline", 0.85
+        return "shallow", 0.70
+
+    def training_quality(self) -> tuple[str, float]:
+        """Quality tier and confidence modifier for the current training_depth."""
+        return self.compute_quality_tier(
+            self.training_depth,
+            self.cfg.get("HMM_TRAINING_CANDLES", 4000),
+            self.cfg.get("HMM_MIN_TRAIN_SAMPLES", 500),
+        )
+
     @staticmethod
     def compute_p_switch(
         posterior: list[float] | tuple[float, ...] | np.ndarray,
@@ -371,8 +1456,108 @@ class RegimeDetector:
         Fit HMM on historical data. Call offline or periodically.
         
         Returns True if training succeeded.
+
+        With HMM_PREV_REGIME_FEATURE, a base model is first fitted on the
+        extractor's columns; its per-bar MAP regimes fill the prev_regime
+        column for this fit and, later, for every inference window.
+        """
+        return self.train_batches([(closes, volumes)])
+
+    def train_batches(self, batches) -> bool:
+        """
+        Fit once on several independent (closes, volumes) segments, e.g. one
+        file per trading day.
+
+        Features are extracted per batch, so EMAs and the volume baseline
+        don't bleed across the gap between segments (each batch pays its own
+        indicator warmup), and the fit treats every batch as a separate
+        sequence. `training_depth` is the total candle count.
+        """
+        pieces = [self.extractor.extract(closes, volumes) for closes, volumes in batches]
+        depth = sum(len(closes) for closes, _ in batches)
+        width = len(self.extractor.feature_names())
+        obs = np.concatenate(pieces) if pieces else np.empty((0, width))
+        lengths = [len(piece) for piece in pieces if len(piece) > 0]
+        if not self.cfg.get("HMM_PREV_REGIME_FEATURE", False):
+            return self.train_features(obs, training_depth=depth, lengths=lengths)
+
+        labeler = self.fork()
+        labeler._regime_labeler = None
+        labeler.cfg["HMM_PREV_REGIME_FEATURE"] = False
+        if not labeler.train_features(obs, training_depth=depth, lengths=lengths):
+            return False
+        previous = self._regime_labeler
+        self._regime_labeler = labeler
+        augmented = np.concatenate([self._with_previous_regime(piece) for piece in pieces])
+        if self.train_features(augmented, training_depth=depth, lengths=lengths):
+            return True
+        self._regime_labeler = previous
+        return False
+
+    @property
+    def n_features(self) -> int:
+        """Observation width the model expects (extractor columns + prev_regime)."""
+        width = len(self.extractor.feature_names())
+        return width + 1 if self.cfg.get("HMM_PREV_REGIME_FEATURE", False) else width
+
+    def feature_names(self) -> list[str]:
+        """Column names of the model's observation rows, in order."""
+        names = self.extractor.feature_names()
+        if self.cfg.get("HMM_PREV_REGIME_FEATURE", False):
+            names.append(PREV_REGIME_FEATURE)
+        return names
+
+    def _with_previous_regime(self, obs: np.ndarray) -> np.ndarray:
+        """
+        Extractor rows plus the prev_regime column when HMM_PREV_REGIME_FEATURE
+        is on (unchanged otherwise). Rows are labeled by the base model `train`
+        fitted; without one (e.g. after `train_features`) the column is neutral.
         """
-        obs = self.extractor.extract(closes, volumes)
+        if not self.cfg.get("HMM_PREV_REGIME_FEATURE", False):
+            return obs
+        regimes: list[Optional[Regime]] = [None] * len(obs)
+        labeler = self._regime_labeler
+        if labeler is not None and labeler._trained and len(obs) > 0:
+            try:
+                posteriors = labeler._posteriors(labeler._standardize(obs))
+                regimes = [
+                    labeler._state_label_map.get(int(i), Regime.RANGING)
+                    for i in np.argmax(posteriors, axis=1)
+                ]
+            except Exception as e:
+                logger.warning("HMM prev_regime labeling failed, using neutral column: %s", e)
+        return append_previous_regime(obs, regimes)
+
+    def train_features(
+        self,
+        rows: np.ndarray | list[list[float]],
+        training_depth: Optional[int] = None,
+        lengths: Optional[list[int]] = None,
+    ) -> bool:
+        """
+        Fit on pre-extracted observation rows (e.g. cached `extract` output).
+
+        Applies the same sample gate, standardization and labeling as
+        `train`. `training_depth` defaults to the row count. `lengths` splits
+        the rows into independent sequences (hmmlearn's convention; None =
+        one sequence). With HMM_TRAIN_SUBSAMPLE = k > 1 only every k-th row
+        (per sequence) is fitted; the sample gate and `training_depth` still
+        count every row, so tiering reflects the full history.
+
+        Returns True if training succeeded. Raises FeatureCountMismatch
+        (before touching any model state) when the row width is wrong.
+        """
+        obs = np.asarray(rows, dtype=float)
+        if obs.size == 0:
+            obs = obs.reshape(0, self.n_features)
+        if obs.ndim != 2:
+            raise ValueError(f"rows must be 2-D, got shape {obs.shape}")
+        if obs.shape[1] != self.n_features:
+            raise FeatureCountMismatch(self.n_features, obs.shape[1])
+        if lengths is not None:
+            lengths = [int(n) for n in lengths]
+            if sum(lengths) != len(obs) or any(n < 1 for n in lengths):
+                raise ValueError(f"lengths {lengths} must be positive and sum to {len(obs)} rows")
 
         if len(obs) < self.cfg["HMM_MIN_TRAIN_SAMPLES"]:
             logger.warning(
@@ -381,27 +1566,63 @@ class RegimeDetector:
             )
             return False
 
-        # Standardize features for stable training
-        self._obs_mean = obs.mean(axis=0)
-        self._obs_std = obs.std(axis=0)
-        self._obs_std[self._obs_std == 0] = 1.0
-        obs_norm = (obs - self._obs_mean) / self._obs_std
+        full_rows = len(obs)
+        obs, lengths = self._subsample_training(obs, lengths)
+        obs_mean, obs_std, obs_norm = self._standardize_training(obs)
 
+        max_iter = self._max_iter()
         model = GaussianHMM(
             n_components=self.cfg["HMM_N_STATES"],
             covariance_type=self.cfg["HMM_COVARIANCE_TYPE"],
-            n_iter=self.cfg["HMM_N_ITER"],
-            random_state=42,
+            n_iter=max_iter,
+            tol=float(self.cfg.get("HMM_TOL", 1e-2)),
+            random_state=int(self.cfg.get("HMM_RANDOM_SEED", 42)),
         )
 
         try:
-            model.fit(obs_norm)
+            if self.cfg.get("HMM_CAPTURE_TRACE", False):
+                iterations, converged = self._fit_traced(model, obs_norm, lengths)
+            else:
+                model.fit(obs_norm, lengths)
+                iterations, converged = self._monitor_outcome(model)
+            self._fit_termination = {
+                "reason": "converged" if converged or iterations < max_iter else "max_iter",
+                "iterations": int(iterations),
+                "max_iter": max_iter,
+            }
+            gamma = np.asarray(model.predict_proba(obs_norm, lengths), dtype=float)
         except Exception as e:
             logger.error("HMM training failed: %s", e)
             return False
 
+        if self._label_map_override is not None:
+            label_map = {i: Regime(r) for i, r in enumerate(self._label_map_override)}
+        else:
+            label_map = self._derive_label_map(model.means_)
+
+        # Effective samples per state (summed responsibilities). A state that
+        # saw almost no data has unreliable emission parameters.
+        occupancy = gamma.sum(axis=0)
+        self._state_effective_counts = [0.0] * len(Regime)
+        for raw_idx, label in label_map.items():
+            self._state_effective_counts[int(label)] = float(occupancy[int(raw_idx)])
+        min_per_state = float(self.cfg.get("HMM_MIN_SAMPLES_PER_STATE", 0) or 0)
+        if min_per_state > 0 and float(occupancy.min()) < min_per_state:
+            logger.warning(
+                "HMM train: state occupancy %s below %.1f per state, keeping previous model",
+                np.array2string(occupancy, precision=1),
+                min_per_state,
+            )
+            return False
+
+        self._blend_transition_inertia(model, label_map)
         self.model = model
-        self._label_states(obs_norm)
+        self._obs_mean = obs_mean
+        self._obs_std = obs_std
+        self._reset_ewma_covars()
+        self._confidence_cusum = 0.0
+        self._install_label_map(label_map)
+        self.training_depth = int(full_rows if training_depth is None else training_depth)
         self._trained = True
         self._last_train_ts = time.time()
 
@@ -414,35 +1635,815 @@ class RegimeDetector:
         )
         return True
 
-    def _label_states(self, obs_norm: np.ndarray):
+    # Attributes a successful train sets; commit_candidate copies them over.
+    _MODEL_FIELDS = (
+        "model",
+        "_obs_mean",
+        "_obs_std",
+        "_state_effective_counts",
+        "_fit_trace",
+        "_fit_termination",
+        "_regime_labeler",
+        "training_depth",
+        "_trained",
+        "_last_train_ts",
+    )
+
+    def evaluate_candidate(self, closes: np.ndarray, volumes: np.ndarray) -> dict:
         """
-        Assign semantic labels to HMM states by inspecting emission means.
-        
+        Decide whether a retrain would help before paying for the swap.
+
+        Fits a candidate on the series minus its last HMM_CANDIDATE_HOLDOUT
+        bars (the live model is untouched), then scores the live model and
+        the candidate on that held-out tail. Only rows from the held-out
+        candles are scored; the bars before them just warm the features up.
+        Log-likelihoods are in raw feature units, so models with different
+        standardization compare.
+        Returns {"current_log_likelihood", "candidate_log_likelihood",
+        "holdout_rows", "recommend"}; recommend is True when the candidate
+        trained and scores at least as well (or nothing is live yet). A
+        trained candidate is kept, as after `begin_retrain`.
+        """
+        holdout = int(self.cfg.get("HMM_CANDIDATE_HOLDOUT", 200))
+        if holdout < 1 or holdout >= len(closes):
+            raise ValueError(
+                f"HMM_CANDIDATE_HOLDOUT must be in [1, {len(closes) - 1}] for "
+                f"{len(closes)} candles, got {holdout}"
+            )
+        trained = self.begin_retrain(closes[:-holdout], volumes[:-holdout])
+
+        fx = self.extractor
+        context = holdout + fx.rsi_period + fx.TAIL_WARMUP_SPANS * fx.warmup_required()
+        tail_c, tail_v = closes[-context:], volumes[-context:]
+        current = self._holdout_log_likelihood(tail_c, tail_v, holdout)
+        scored = self._candidate._holdout_log_likelihood(tail_c, tail_v, holdout) if trained else None
+        index = fx.extract_with_index(tail_c, tail_v)[1]
+        return {
+            "current_log_likelihood": current,
+            "candidate_log_likelihood": scored,
+            "holdout_rows": int(np.sum(index >= len(tail_c) - holdout)),
+            "recommend": scored is not None and (current is None or scored >= current),
+        }
+
+    def _holdout_log_likelihood(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        holdout: int,
+    ) -> Optional[float]:
+        """
+        `score` of the rows from the last `holdout` candles only (earlier
+        candles are feature warmup), with the z-score Jacobian removed (raw
+        feature units). None when untrained or no held-out row survives.
+        """
+        if not self._trained or self.model is None:
+            return None
+        raw, index = self.extractor.extract_with_index(closes, volumes)
+        obs = self._with_previous_regime(raw)[index >= len(closes) - int(holdout)]
+        if len(obs) == 0:
+            return None
+        try:
+            total = float(self.model.score(self._standardize(obs)))
+        except Exception as e:
+            logger.warning("HMM score failed: %s", e)
+            return None
+        return float(total - len(obs) * np.sum(np.log(self._obs_std)))
+
+    def begin_retrain(self, closes: np.ndarray, volumes: np.ndarray) -> bool:
+        """
+        Train a candidate model held apart from the live one: `update` keeps
+        using the live model until `commit_candidate` swaps the candidate in
+        (or `rollback_candidate` drops it). Replaces any pending candidate;
+        returns False, leaving none pending, when training fails.
+        """
+        candidate = self.fork()
+        candidate._candidate = None
+        self._candidate = candidate if candidate.train(closes, volumes) else None
+        return self._candidate is not None
+
+    def has_candidate(self) -> bool:
+        """True while a retrained candidate awaits commit or rollback."""
+        return self._candidate is not None
+
+    def rollback_candidate(self) -> bool:
+        """Discard the pending candidate; False when there was none."""
+        pending = self._candidate is not None
+        self._candidate = None
+        return pending
+
+    def commit_candidate(self) -> bool:
+        """
+        Swap in the pending candidate in one step: model, standardizer,
+        label map, training depth and train time all change together, so
+        no update sees a half-installed model. False when none is pending.
+        """
+        candidate = self._candidate
+        if candidate is None:
+            return False
+        self._candidate = None
+        for name in self._MODEL_FIELDS:
+            setattr(self, name, getattr(candidate, name))
+        self._reset_ewma_covars()
+        self._confidence_cusum = 0.0
+        self._install_label_map(candidate._state_label_map)
+        logger.info("HMM candidate committed (%d candles)", self.training_depth)
+        return True
+
+    def _blend_transition_inertia(self, model, label_map: dict[int, Regime]) -> None:
+        """
+        Pull a freshly fitted transmat toward the previous model's (or
+        HMM_TRANSITION_PRIOR) by HMM_TRANSITION_INERTIA, so daily retrains
+        don't swing dwell behavior. Matrices are aligned by regime label,
+        since raw state order is arbitrary between fits; rows are
+        re-normalized after blending. Skipped (with a warning) when either
+        label map isn't one state per regime.
+        """
+        inertia = min(1.0, max(0.0, float(self.cfg.get("HMM_TRANSITION_INERTIA", 0.0) or 0.0)))
+        if inertia <= 0.0:
+            return
+
+        def raw_by_label(mapping):
+            raw = {int(label): int(idx) for idx, label in mapping.items()}
+            if len(mapping) != len(Regime) or sorted(raw) != [int(r) for r in Regime]:
+                return None
+            return [raw[int(r)] for r in Regime]
+
+        new_order = raw_by_label(label_map)
+        if new_order is None:
+            logger.warning("HMM transition inertia skipped: label map %s is not one-to-one", label_map)
+            return
+        prior = self.cfg.get("HMM_TRANSITION_PRIOR")
+        if prior is not None:
+            reference = np.asarray(prior, dtype=float)
+            if reference.shape != (len(Regime), len(Regime)):
+                raise ValueError(f"HMM_TRANSITION_PRIOR must be 3x3, got shape {reference.shape}")
+        else:
+            if not self._trained or self.model is None:
+                return
+            old_order = raw_by_label(self._state_label_map)
+            if old_order is None:
+                logger.warning("HMM transition inertia skipped: previous label map is not one-to-one")
+                return
+            reference = np.asarray(self.model.transmat_, dtype=float)[np.ix_(old_order, old_order)]
+
+        fresh = np.asarray(model.transmat_, dtype=float)[np.ix_(new_order, new_order)]
+        blended = (1.0 - inertia) * fresh + inertia * reference
+        blended = blended / np.maximum(blended.sum(axis=1, keepdims=True), 1e-300)
+        transmat = np.empty_like(blended)
+        transmat[np.ix_(new_order, new_order)] = blended
+        model.transmat_ = transmat
+
+    def _max_iter(self) -> int:
+        """EM iteration budget: HMM_N_ITER, capped by HMM_MAX_ITER when set."""
+        n_iter = int(self.cfg["HMM_N_ITER"])
+        ceiling = self.cfg.get("HMM_MAX_ITER")
+        if ceiling is None:
+            return n_iter
+        if int(ceiling) < 1:
+            raise ValueError(f"HMM_MAX_ITER must be >= 1 or None, got {ceiling!r}")
+        return min(n_iter, int(ceiling))
+
+    @staticmethod
+    def _monitor_outcome(model) -> tuple[int, bool]:
+        """(iterations run, tolerance reached) from hmmlearn's ConvergenceMonitor."""
+        monitor = getattr(model, "monitor_", None)
+        iterations = int(getattr(monitor, "iter", 0) or 0)
+        history = list(getattr(monitor, "history", []) or [])
+        converged = len(history) >= 2 and history[-1] - history[-2] < float(model.tol)
+        return iterations, converged
+
+    def fit_termination(self) -> Optional[dict]:
+        """
+        Why the last EM run stopped: {"reason": "converged" | "max_iter",
+        "iterations", "max_iter"}, where max_iter is HMM_N_ITER capped by
+        HMM_MAX_ITER. "converged" means the log-likelihood gain fell below
+        HMM_TOL before the budget ran out. None before the first fit.
+        """
+        return None if self._fit_termination is None else dict(self._fit_termination)
+
+    def _fit_traced(
+        self, model, obs_norm: np.ndarray, lengths: Optional[list[int]] = None
+    ) -> tuple[int, bool]:
+        """
+        Fit one EM iteration at a time, recording the parameters after each.
+
+        The first call initializes parameters (seeded as usual); later calls
+        use init_params="" so they continue from where the previous stopped.
+        Stops after the iteration budget or once the log-likelihood gain
+        drops below the model's tol, mirroring a plain fit. Returns
+        (iterations run, tolerance reached).
+        """
+        n_iter = int(model.n_iter)
+        cap = max(1, int(self.cfg.get("HMM_TRACE_MAX_ITER", 200)))
+        trace: list[dict] = []
+        self._fit_trace = trace
+        model.n_iter = 1
+        prev = -np.inf
+        iterations, converged = 0, False
+        for _ in range(max(1, n_iter)):
+            model.fit(obs_norm, lengths)
+            iterations += 1
+            model.init_params = ""
+            loglik = float(model.score(obs_norm, lengths))
+            trace.append({
+                "log_likelihood": loglik,
+                "transmat": np.array(model.transmat_, dtype=float),
+                "means": np.array(model.means_, dtype=float),
+            })
+            if len(trace) > cap:
+                del trace[0]
+            if loglik - prev < float(model.tol):
+                converged = True
+                break
+            prev = loglik
+        model.n_iter = n_iter
+        return iterations, converged
+
+    def last_fit_trace(self) -> Optional[list[dict]]:
+        """
+        Per-iteration {"log_likelihood", "transmat", "means"} from the most
+        recent fit (successful or not), or None unless HMM_CAPTURE_TRACE was
+        on. Holds at most HMM_TRACE_MAX_ITER entries, the most recent kept.
+        """
+        if self._fit_trace is None:
+            return None
+        return list(self._fit_trace)
+
+    def _subsample_training(
+        self, obs: np.ndarray, lengths: Optional[list[int]]
+    ) -> tuple[np.ndarray, Optional[list[int]]]:
+        """Every HMM_TRAIN_SUBSAMPLE-th row of each sequence (unchanged for 1)."""
+        step = self.cfg.get("HMM_TRAIN_SUBSAMPLE", 1)
+        if not isinstance(step, (int, np.integer)) or isinstance(step, bool) or step < 1:
+            raise ValueError(f"HMM_TRAIN_SUBSAMPLE must be an integer >= 1, got {step!r}")
+        if step == 1:
+            return obs, lengths
+        if lengths is None:
+            return obs[::step], None
+        bounds = np.cumsum([0] + list(lengths))
+        pieces = [obs[start:stop:step] for start, stop in zip(bounds[:-1], bounds[1:])]
+        return np.concatenate(pieces), [len(piece) for piece in pieces]
+
+    def _standardize_training(self, obs: np.ndarray) -> tuple[np.ndarray, np.ndarray, np.ndarray]:
+        """
+        Training z-score: (mean, std, standardized rows).
+
+        Constant columns (e.g. a feed reporting volume as all 1.0) keep a unit
+        std and get seeded N(0, HMM_CONSTANT_JITTER) noise in the fit input,
+        so Baum-Welch isn't handed an exactly degenerate column. Inference
+        still sees the column at 0, which is the same for every state.
+        """
+        obs_mean = obs.mean(axis=0)
+        obs_std = obs.std(axis=0)
+        constant = obs_std <= 1e-12
+        obs_std[constant] = 1.0
+        obs_norm = (obs - obs_mean) / obs_std
+        jitter = float(self.cfg.get("HMM_CONSTANT_JITTER", 1e-3) or 0.0)
+        if constant.any() and jitter > 0.0:
+            logger.warning(
+                "HMM train: constant feature column(s) %s; adding seeded jitter",
+                np.flatnonzero(constant).tolist(),
+            )
+            rng = np.random.default_rng(int(self.cfg.get("HMM_RANDOM_SEED", 42)))
+            obs_norm[:, constant] += rng.normal(0.0, jitter, size=(len(obs), int(constant.sum())))
+        return obs_mean, obs_std, obs_norm
+
+    @staticmethod
+    def _derive_label_map(means: np.ndarray) -> dict[int, Regime]:
+        """
+        Assign semantic labels to HMM states by inspecting emission means.
+        
         The EMA spread (feature index 1) is the primary discriminator:
             - highest mean  -> BULLISH
             - lowest mean   -> BEARISH
             - middle        -> RANGING
+
+        Exact ties fall back to the MACD histogram slope mean (feature 0),
+        then to the raw state index, so identical fits always label the same.
         """
-        means = self.model.means_  # shape: (n_states, n_features)
+        means = np.asarray(means)
         ema_spread_means = means[:, 1]  # feature 1 = ema_spread_pct
 
-        sorted_indices = np.argsort(ema_spread_means)
-        self._state_label_map = {
-            sorted_indices[0]: Regime.BEARISH,
-            sorted_indices[1]: Regime.RANGING,
-            sorted_indices[2]: Regime.BULLISH,
+        # lexsort is stable and sorts by the last key first.
+        sorted_indices = np.lexsort((means[:, 0], ema_spread_means))
+        return {
+            int(sorted_indices[0]): Regime.BEARISH,
+            int(sorted_indices[1]): Regime.RANGING,
+            int(sorted_indices[2]): Regime.BULLISH,
         }
 
+    def _install_label_map(self, label_map: dict[int, Regime]) -> None:
+        """Swap in a new state→regime map, remembering the one it replaces."""
+        self._previous_label_map = dict(self._state_label_map)
+        self._state_label_map = {int(k): Regime(v) for k, v in label_map.items()}
+        if self.label_map_changed():
+            logger.warning(
+                "HMM label map changed on retrain: %s -> %s (bias sign may flip)",
+                self._previous_label_map, self._state_label_map,
+            )
+
+    def set_label_map(self, mapping: list[int]) -> None:
+        """
+        Pin the raw-state → regime mapping instead of deriving it from the
+        EMA-spread means (for assets where that feature doesn't separate).
+
+        `mapping[raw_state]` is a Regime index; it must have one entry per
+        HMM state and use every regime at least once. The override applies
+        immediately, survives retrains and snapshots until cleared.
+        """
+        n_states = int(self.cfg["HMM_N_STATES"])
+        if self.model is not None and getattr(self.model, "transmat_", None) is not None:
+            n_states = int(np.asarray(self.model.transmat_).shape[0])
+        values = [int(v) for v in mapping]
+        if len(values) != n_states:
+            raise ValueError(f"label map needs {n_states} entries, got {len(values)}")
+        if any(v < 0 or v >= len(Regime) for v in values):
+            raise ValueError(f"label map entries must be in [0, {len(Regime)}), got {values}")
+        if set(values) != set(int(r) for r in Regime):
+            raise ValueError(f"label map must cover every regime, got {values}")
+        self._label_map_override = values
+        self._install_label_map({i: Regime(v) for i, v in enumerate(values)})
+
+    def clear_label_map(self) -> None:
+        """Drop the manual override; the next train derives labels again."""
+        self._label_map_override = None
+
+    @property
+    def label_map_override(self) -> Optional[list[int]]:
+        return None if self._label_map_override is None else list(self._label_map_override)
+
+    @property
+    def label_map(self) -> dict[int, Regime]:
+        """Current raw HMM state index → Regime mapping."""
+        return dict(self._state_label_map)
+
+    @property
+    def previous_label_map(self) -> dict[int, Regime]:
+        """Mapping in force before the last successful train (empty if none)."""
+        return dict(self._previous_label_map)
+
+    def label_map_changed(self) -> bool:
+        """
+        True when the last retrain relabeled any raw state. A first-ever
+        train is not a change. Callers may log this or veto the retrain.
+        """
+        if not self._previous_label_map:
+            return False
+        return self._previous_label_map != self._state_label_map
+
+    def state_effective_counts(self) -> list[float]:
+        """
+        Summed responsibilities per regime from the most recent fit attempt.
+
+        Indexed by Regime. Populated even when the fit was rejected by
+        HMM_MIN_SAMPLES_PER_STATE so the starved state can be diagnosed.
+        """
+        return list(self._state_effective_counts)
+
     # --- Inference -----------------------------------------------------------
 
-    def update(self, closes: np.ndarray, volumes: np.ndarray) -> RegimeState:
+    def _standardize(self, obs: np.ndarray) -> np.ndarray:
+        """Apply the training-time z-score to extracted observation rows."""
+        return (obs - self._obs_mean) / self._obs_std
+
+    def score(self, closes: np.ndarray, volumes: np.ndarray) -> Optional[float]:
+        """
+        Total log-likelihood of the extracted sequence under the model.
+
+        Mirrors hmmlearn's ``score`` so results can be cross-checked against
+        a reference fit. Returns None when untrained or no rows survive
+        extraction.
+        """
+        if not self._trained or self.model is None:
+            return None
+        obs = self._with_previous_regime(self.extractor.extract(closes, volumes))
+        if len(obs) == 0:
+            return None
+        try:
+            return float(self.model.score(self._standardize(obs)))
+        except Exception as e:
+            logger.warning("HMM score failed: %s", e)
+            return None
+
+    def change_point_score(self, closes: np.ndarray, volumes: np.ndarray) -> Optional[float]:
+        """
+        Likelihood-ratio evidence (nats) that a regime change happened at the
+        boundary HMM_CHANGE_POINT_WINDOW bars back.
+
+        Compares the recent sub-window's fit when started from the state the
+        preceding sub-window settled into against its best single-state
+        start. Zero means the older window's state explains the recent bars;
+        large values mean it does not. None when untrained or short of data.
+        """
+        if not self._trained or self.model is None:
+            return None
+        obs = self._with_previous_regime(self.extractor.extract(closes, volumes))
+        if len(obs) == 0:
+            return None
+        return self._change_point_from_obs(self._standardize(obs))
+
+    def _change_point_from_obs(self, obs_norm: np.ndarray) -> Optional[float]:
+        """change_point_score on already standardized rows."""
+        window = max(1, int(self.cfg.get("HMM_CHANGE_POINT_WINDOW", 12)))
+        if len(obs_norm) < 2 * window:
+            return None
+        try:
+            log_b = self._emission_log_densities(obs_norm[-2 * window:])
+            transmat = np.asarray(self.model.transmat_, dtype=float)
+            _, older_alpha, _ = _forward_backward(
+                self.model.startprob_, transmat, log_b[:window]
+            )
+            settled = older_alpha[-1] @ transmat
+            recent = log_b[window:]
+            conditioned, _, _ = _forward_backward(settled, transmat, recent)
+            n_states = transmat.shape[0]
+            best = max(
+                _forward_backward(np.eye(n_states)[k], transmat, recent)[0]
+                for k in range(n_states)
+            )
+        except Exception as e:
+            logger.warning("HMM change-point score failed: %s", e)
+            return None
+        return max(0.0, float(best - conditioned))
+
+    def viterbi_regimes(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        timestamps: Optional[list] = None,
+    ) -> Optional[list]:
+        """
+        Most likely regime path (Viterbi) over every extracted row.
+
+        Offline labeling helper. Without `timestamps` the path is aligned with
+        the tail of the input, since indicator warmup rows are dropped by the
+        extractor. With a `timestamps` sequence parallel to `closes`, returns
+        (timestamp, Regime) pairs attributed to the candle each row came from.
+        None when untrained or no rows survive extraction.
+        """
+        if not self._trained or self.model is None:
+            return None
+        obs, index = self.extractor.extract_with_index(closes, volumes)
+        obs = self._with_previous_regime(obs)
+        if len(obs) == 0:
+            return None
+        try:
+            path = self.model.predict(self._standardize(obs))
+        except Exception as e:
+            logger.warning("HMM Viterbi decode failed: %s", e)
+            return None
+        labels = [self._state_label_map[int(k)] for k in path]
+        return self._with_timestamps(labels, index, closes, timestamps)
+
+    @staticmethod
+    def _with_timestamps(labels: list, index: np.ndarray, closes, timestamps) -> list:
+        """Pair per-row labels with their candle's timestamp (labels as-is when None)."""
+        if timestamps is None:
+            return labels
+        if len(timestamps) != len(closes):
+            raise ValueError(
+                f"timestamps has {len(timestamps)} entries for {len(closes)} candles"
+            )
+        return [(timestamps[int(i)], label) for i, label in zip(index, labels)]
+
+    def _accumulate_confidence_drift(self, confidence: float) -> None:
+        """One-sided (lower) CUSUM step: S = max(0, S + ref - slack - confidence)."""
+        ref = float(self.cfg.get("HMM_CUSUM_REFERENCE", 0.35))
+        slack = float(self.cfg.get("HMM_CUSUM_SLACK", 0.05))
+        self._confidence_cusum = max(
+            0.0, self._confidence_cusum + ref - slack - float(confidence)
+        )
+
+    def confidence_drift(self) -> float:
+        """
+        CUSUM of confidence shortfalls below HMM_CUSUM_REFERENCE. Stays near
+        zero while confidence holds, climbs under a sustained drop and decays
+        back as it recovers. Reset by a successful train.
+        """
+        return float(self._confidence_cusum)
+
+    def confidence_drift_alarm(self) -> bool:
+        """True when confidence_drift() exceeds HMM_CUSUM_THRESHOLD."""
+        return self._confidence_cusum > float(self.cfg.get("HMM_CUSUM_THRESHOLD", 3.0))
+
+    def posterior_labels(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        timestamps: Optional[list] = None,
+    ) -> Optional[list]:
+        """
+        Per-bar MAP regime: argmax of the smoothed posterior at each row.
+
+        Each label is individually most likely, unlike `viterbi_regimes`
+        whose jointly most likely path can disagree bar-by-bar (and the MAP
+        sequence may even contain transitions the model forbids). Aligned
+        with the tail of the input, or (timestamp, Regime) pairs when
+        `timestamps` is given; None when untrained or no rows.
+        """
+        if not self._trained or self.model is None:
+            return None
+        obs, index = self.extractor.extract_with_index(closes, volumes)
+        obs = self._with_previous_regime(obs)
+        if len(obs) == 0:
+            return None
+        try:
+            states = self._posterior_argmax(self._standardize(obs))
+        except Exception as e:
+            logger.warning("HMM posterior decode failed: %s", e)
+            return None
+        labels = [self._state_label_map[int(k)] for k in states]
+        return self._with_timestamps(labels, index, closes, timestamps)
+
+    def average_direction(
+        self, closes: np.ndarray, volumes: np.ndarray, window: int
+    ) -> Optional[float]:
+        """
+        Mean of P(bull) - P(bear) over the smoothed posteriors of the last
+        `window` rows, in [-1, 1]: a slow directional signal for position
+        sizing. Uses fewer rows if fewer survive extraction; None when
+        untrained or no rows.
+        """
+        window = int(window)
+        if window < 1:
+            raise ValueError(f"window must be >= 1, got {window}")
+        if not self._trained or self.model is None:
+            return None
+        obs = self._with_previous_regime(self.extractor.extract(closes, volumes))
+        if len(obs) == 0:
+            return None
+        posteriors = self._posteriors(self._standardize(obs))[-window:]
+        labeled = np.array([self._remap_probs(p) for p in posteriors])
+        direction = labeled[:, Regime.BULLISH] - labeled[:, Regime.BEARISH]
+        return float(np.clip(direction.mean(), -1.0, 1.0))
+
+    def calibration_report(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        horizon: int = 1,
+        n_buckets: int = 5,
+    ) -> Optional[dict]:
+        """
+        Reliability of `confidence` against what price did next.
+
+        Every extracted bar is classified from its filtered posterior (no
+        look-ahead); a BULLISH/BEARISH call agrees when the close `horizon`
+        bars later moved the same way. RANGING calls make no directional
+        claim and are skipped, as are the last `horizon` bars. Returns
+        {"horizon", "scored", "buckets"} where buckets maps equal-width
+        confidence bins over [0, 1] ("0.00-0.20", ...) to {"count",
+        "agreement"} (agreement None for an empty bin). None when untrained
+        or no rows.
+        """
+        horizon = int(horizon)
+        n_buckets = int(n_buckets)
+        if horizon < 1:
+            raise ValueError(f"horizon must be >= 1, got {horizon}")
+        if n_buckets < 1:
+            raise ValueError(f"n_buckets must be >= 1, got {n_buckets}")
+        if not self._trained or self.model is None:
+            return None
+        obs, index = self.extractor.extract_with_index(closes, volumes)
+        obs = self._with_previous_regime(obs)
+        if len(obs) == 0:
+            return None
+
+        c = np.asarray(closes, dtype=float)
+        filtered = self._filtered_posteriors(self._standardize(obs))
+        metric = self.cfg.get("HMM_CONFIDENCE_METRIC", "margin")
+        counts = np.zeros(n_buckets, dtype=int)
+        hits = np.zeros(n_buckets, dtype=int)
+        for row, k in zip(filtered, index):
+            if k + horizon >= len(c):
+                continue
+            labeled = self._remap_probs(row)
+            direction = Regime(int(np.argmax(labeled))).direction()
+            if direction == 0:
+                continue
+            bucket = min(n_buckets - 1, int(self.compute_confidence(labeled, metric) * n_buckets))
+            counts[bucket] += 1
+            hits[bucket] += int(np.sign(c[k + horizon] - c[k]) == direction)
+
+        buckets = {}
+        for b in range(n_buckets):
+            label = f"{b / n_buckets:.2f}-{(b + 1) / n_buckets:.2f}"
+            agreement = float(hits[b] / counts[b]) if counts[b] else None
+            buckets[label] = {"count": int(counts[b]), "agreement": agreement}
+        return {"horizon": horizon, "scored": int(counts.sum()), "buckets": buckets}
+
+    def _filtered_posteriors(self, obs_norm: np.ndarray) -> np.ndarray:
+        """Forward-only (filtered) state posteriors (T, n_states) in raw model order."""
+        _, filtered, _ = _forward_backward(
+            self.model.startprob_, self.model.transmat_, self._emission_log_densities(obs_norm)
+        )
+        return filtered
+
+    def _posterior_argmax(self, obs_norm: np.ndarray) -> np.ndarray:
+        """Raw state index maximizing the smoothed posterior at each row."""
+        return np.argmax(self._posteriors(obs_norm), axis=1)
+
+    def last_update_changed_regime(self) -> bool:
+        """
+        True when the most recent update moved the argmax regime off the one
+        the previous update emitted. No hysteresis; the tertiary transition
+        confirmation in bot.py is separate.
+        """
+        return self._regime_changed
+
+    def regime_at(self, closes: np.ndarray, volumes: np.ndarray, index: int) -> RegimeState:
+        """
+        Smoothed regime at candle `index` of the supplied series (for chart
+        annotation). Runs forward-backward over every extracted row; the live
+        state is not touched.
+
+        Raises ValueError when untrained, when `index` is outside the series
+        or when it falls in the indicator warmup that extraction drops.
+        """
+        if not self._trained or self.model is None:
+            raise ValueError("regime_at requires a trained model")
+        n = len(closes)
+        if not 0 <= int(index) < n:
+            raise ValueError(f"index {index} out of range for {n} candles")
+        obs = self._with_previous_regime(self.extractor.extract(closes, volumes))
+        offset = n - len(obs)
+        row = int(index) - offset
+        if row < 0:
+            raise ValueError(f"index {index} falls in the {offset}-candle feature warmup")
+        posteriors = self._posteriors(self._standardize(obs))
+        return self._build_state(self._remap_probs(posteriors[row]), len(obs))
+
+    def update(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        as_of_ts: Optional[float] = None,
+    ) -> RegimeState:
         """
         Run HMM inference on recent data. Call on each rebalancer tick.
         
         Uses the last HMM_INFERENCE_WINDOW observations for the forward pass.
+        `as_of_ts` stamps `last_update_ts` instead of the wall clock, so a
+        replay can supply each bar's own timestamp (None = time.time()).
         Returns updated RegimeState.
+
+        With HMM_UPDATE_FAST_PATH, a call whose input has the same length,
+        last close/volume and `as_of_ts` as the previous successful one (same
+        model, and no other emission in between) returns the cached state
+        without re-extracting or re-running inference. The
+        fast path stays off while HMM_STALL_DECAY_UPDATES is set, so the
+        staleness decay keeps advancing on repeated inputs.
+        """
+        key = self._input_key(closes, volumes, as_of_ts)
+        if key is not None and key == self._last_input_key:
+            self._fast_path_hits += 1
+            return self.state
+
+        obs_tail = self._prepare_inference(closes, volumes)
+        if obs_tail is None:
+            return self.state
+
+        raw_probs = self._infer_last(obs_tail)
+        if raw_probs is None:
+            return self.state
+        state = self._emit_state(self._remap_probs(raw_probs), len(obs_tail), as_of_ts)
+        self._last_input_key = key
+        self._track_hit_rate(closes)
+        return state
+
+    def _track_hit_rate(self, closes: np.ndarray) -> None:
+        """
+        Resolve the pending directional call against the newest close, then
+        register the regime just emitted as the next pending call. A call
+        resolves on the first later update whose input tail differs (length
+        or last close) and is a hit when price moved the way it pointed;
+        RANGING claims no direction and isn't tracked.
+        """
+        if len(closes) == 0 or not np.isfinite(float(closes[-1])):
+            return
+        tail = (len(closes), float(closes[-1]))
+        pending = self._pending_call
+        if pending is not None:
+            if (pending[1], pending[2]) == tail:
+                return
+            self._hit_outcomes.append(bool(np.sign(tail[1] - pending[2]) == pending[0]))
+            window = max(1, int(self.cfg.get("HMM_HIT_RATE_WINDOW", 200)))
+            del self._hit_outcomes[:-window]
+        direction = Regime(int(self.state.regime)).direction()
+        self._pending_call = (direction, tail[0], tail[1]) if direction != 0 else None
+
+    def rolling_hit_rate(self) -> Optional[float]:
+        """
+        Share of the last HMM_HIT_RATE_WINDOW resolved BULLISH/BEARISH calls
+        whose next close moved the predicted way; None until one resolves.
+        """
+        if not self._hit_outcomes:
+            return None
+        return float(np.mean(self._hit_outcomes))
+
+    def _input_key(self, closes, volumes, as_of_ts=None) -> Optional[tuple]:
         """
-        obs = self.extractor.extract(closes, volumes)
+        Cheap identity of an update input for the fast path (None = disabled).
+        Covers the inference window and the runtime config (including the
+        extractor's), so a setter or cfg edit forces a fresh inference.
+        """
+        if not self.cfg.get("HMM_UPDATE_FAST_PATH", False):
+            return None
+        if int(self.cfg.get("HMM_STALL_DECAY_UPDATES", 0) or 0) > 0:
+            return None
+        if not self._trained or self.model is None or len(closes) == 0 or len(volumes) == 0:
+            return None
+        return (
+            len(closes),
+            float(closes[-1]),
+            float(volumes[-1]),
+            id(self.model),
+            float(self._last_train_ts),
+            self.effective_inference_window(),
+            repr(sorted(self.cfg.items())),
+            repr(self.extractor.config()),
+            as_of_ts,
+        )
+
+    def update_with_prior(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        prior: list[float] | np.ndarray,
+    ) -> RegimeState:
+        """
+        Like `update`, but seed the forward pass with `prior` instead of the
+        learned initial distribution (e.g. a higher-timeframe posterior).
+
+        `prior` is in Regime order and must have n_states entries; it is
+        normalized before use.
+        """
+        obs_tail = self._prepare_inference(closes, volumes)
+        if obs_tail is None:
+            return self.state
+
+        p = np.asarray(prior, dtype=float).reshape(-1)
+        n_states = int(np.asarray(self.model.transmat_).shape[0])
+        if p.size != n_states:
+            raise ValueError(f"prior must have {n_states} entries, got {p.size}")
+        p = np.where(np.isfinite(p), np.clip(p, 0.0, None), 0.0)
+        if float(p.sum()) <= 1e-12:
+            raise ValueError("prior must have positive mass")
+        raw_prior = np.zeros(n_states)
+        for raw_idx, label in self._state_label_map.items():
+            raw_prior[int(raw_idx)] = p[int(label)]
+        if float(raw_prior.sum()) <= 1e-12:
+            raw_prior = np.ones(n_states)
+        raw_prior = raw_prior / raw_prior.sum()
+
+        raw_probs = self._infer_last(obs_tail, start_probs=raw_prior)
+        if raw_probs is None:
+            return self.state
+        return self._emit_state(self._remap_probs(raw_probs), len(obs_tail))
+
+    def update_lookahead(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        lookahead_weight: float,
+    ) -> RegimeState:
+        """
+        Like `update`, but lean into the model's one-step-ahead forecast.
+
+        Emits (1 - w) * p + w * (p @ A) for the last-bar posterior p, with
+        w clamped to [0, 1]: 0 reproduces `update`, 1 is the pure forecast.
+        """
+        obs_tail = self._prepare_inference(closes, volumes)
+        if obs_tail is None:
+            return self.state
+
+        raw_probs = self._infer_last(obs_tail)
+        if raw_probs is None:
+            return self.state
+        w = max(0.0, min(1.0, float(lookahead_weight)))
+        forecast = raw_probs @ np.asarray(self.model.transmat_, dtype=float)
+        blended = (1.0 - w) * raw_probs + w * forecast
+        blended = blended / max(float(blended.sum()), 1e-300)
+        return self._emit_state(self._remap_probs(blended), len(obs_tail))
+
+    def _prepare_inference(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+    ) -> Optional[np.ndarray]:
+        """
+        Extract features, record the latest raw row and return the inference
+        tail, or None when inference cannot run (untrained / no rows).
+        """
+        cap = self.max_history()
+        if cap is not None and len(closes) > cap:
+            closes = closes[-cap:]
+            volumes = volumes[-cap:]
+        # Only the inference window is needed, so skip filtering old history.
+        window = self.effective_inference_window()
+        self._window_capped = window < self._configured_inference_window()
+        self._requested_window = int(window)
+        obs = self.extractor.extract_tail(closes, volumes, window)
         if len(obs) > 0:
             latest = obs[-1]
             snap = IndicatorSnapshot(
@@ -459,68 +2460,552 @@ class RegimeDetector:
 
         if not self._trained or self.model is None:
             logger.debug("HMM not trained yet, returning default RANGING state")
-            return self.state
+            return None
 
         if len(obs) == 0:
-            return self.state
+            return None
+
+        return self._with_previous_regime(obs)
+
+    def _posteriors(
+        self,
+        obs_norm: np.ndarray,
+        start_probs: Optional[np.ndarray] = None,
+    ) -> np.ndarray:
+        """
+        Smoothed state posteriors (T, n_states) in raw model order.
+
+        Delegates to hmmlearn unless a custom start distribution or emission
+        option is in play, in which case the forward-backward pass runs on
+        the fitted parameters.
+        """
+        if start_probs is None and not self._custom_emissions():
+            _, posteriors = self.model.score_samples(obs_norm)
+            return np.asarray(posteriors, dtype=float)
+        if start_probs is None:
+            start_probs = self.model.startprob_
+        _, _, smoothed = _forward_backward(
+            start_probs, self.model.transmat_, self._emission_log_densities(obs_norm)
+        )
+        return smoothed
+
+    def _custom_emissions(self) -> bool:
+        """True when inference must bypass hmmlearn's stock Gaussian emissions."""
+        return (
+            bool(self.cfg.get("HMM_ROBUST_EMISSION", False))
+            or bool(self.cfg.get("HMM_EWMA_COVARIANCE", False))
+            or self._temperature() != 1.0
+            or float(self.cfg.get("HMM_EMISSION_FLOOR", 0.0) or 0.0) > 0.0
+        )
+
+    def _temperature(self) -> float:
+        """HMM_TEMPERATURE; raises ValueError unless finite and positive."""
+        t = float(self.cfg.get("HMM_TEMPERATURE", 1.0))
+        if not np.isfinite(t) or t <= 0.0:
+            raise ValueError(f"HMM_TEMPERATURE must be positive, got {t!r}")
+        return t
+
+    def _emission_log_densities(self, obs_norm: np.ndarray) -> np.ndarray:
+        """
+        Per-state emission log-densities honoring the emission options.
 
-        # Use tail window for inference
-        window = self.cfg["HMM_INFERENCE_WINDOW"]
-        obs_tail = obs[-window:]
-        obs_norm = (obs_tail - self._obs_mean) / self._obs_std
+        Dividing by HMM_TEMPERATURE calibrates the posteriors: the diagonal
+        emissions treat correlated features as independent evidence, so
+        T > 1 undoes some of that overconfidence (T < 1 sharpens).
+
+        HMM_EMISSION_FLOOR then bounds each state's likelihood below at that
+        fraction of the row's most likely state, so one extreme bar can't rule
+        a state out entirely; larger floors keep the posterior less peaked.
+        """
+        huber_delta = None
+        if self.cfg.get("HMM_ROBUST_EMISSION", False):
+            huber_delta = max(1e-6, float(self.cfg.get("HMM_ROBUST_DELTA", 1.5)))
+        log_b = _state_log_densities(
+            obs_norm, self.model.means_, self._effective_covars(), huber_delta=huber_delta
+        )
+        log_b = log_b / self._temperature()
+        floor = float(self.cfg.get("HMM_EMISSION_FLOOR", 0.0) or 0.0)
+        if floor > 0.0:
+            if not np.isfinite(floor) or floor > 1.0:
+                raise ValueError(f"HMM_EMISSION_FLOOR must be in [0, 1], got {floor!r}")
+            log_b = np.maximum(log_b, log_b.max(axis=1, keepdims=True) + np.log(floor))
+        return log_b
+
+    def _reset_ewma_covars(self) -> None:
+        self._ewma_covars = None
+        self._ewma_last_row = None
+
+    def _effective_covars(self) -> np.ndarray:
+        """Emission covariances: the EWMA estimate when enabled, else the fitted ones."""
+        if not self.cfg.get("HMM_EWMA_COVARIANCE", False):
+            return _model_covars(self.model)
+        if self._ewma_covars is None:
+            self._ewma_covars = _model_covars(self.model).copy()
+        return self._ewma_covars
+
+    def _update_ewma_covars(self, row: np.ndarray, posterior: np.ndarray) -> None:
+        """
+        Fold the newest standardized row into the per-state covariance EWMA,
+        each state weighted by HMM_EWMA_DECAY times its posterior. The
+        estimate starts at the fitted covariances, so it drifts from them
+        only as recent residuals accumulate. Repeated rows are skipped.
+        """
+        row = np.asarray(row, dtype=float)
+        if self._ewma_last_row is not None and np.array_equal(row, self._ewma_last_row):
+            return
+        self._ewma_last_row = row.copy()
+        covars = self._effective_covars()
+        decay = min(1.0, max(0.0, float(self.cfg.get("HMM_EWMA_DECAY", 0.02))))
+        means = np.asarray(self.model.means_, dtype=float)
+        for k in range(covars.shape[0]):
+            w = decay * float(posterior[k])
+            if w <= 0.0:
+                continue
+            resid = row - means[k]
+            sample = resid * resid if covars.ndim == 2 else np.outer(resid, resid)
+            covars[k] = (1.0 - w) * covars[k] + w * sample
+
+    def _update_online_transitions(self, previous: np.ndarray, current: np.ndarray) -> None:
+        """
+        Move each transmat row toward the state distribution the window's
+        last step landed in, by HMM_ONLINE_TRANSITIONS_RATE times the smoothed
+        probability of having come from that row's state. Rows stay
+        stochastic and means/covars are never touched, so regimes keep their
+        meaning while dwell behavior tracks recent bars. The next retrain
+        replaces the adapted matrix. Repeated rows are skipped by the caller.
+        """
+        rate = min(1.0, max(0.0, float(self.cfg.get("HMM_ONLINE_TRANSITIONS_RATE", 0.01) or 0.0)))
+        if rate <= 0.0:
+            return
+        previous = np.asarray(previous, dtype=float)
+        current = np.asarray(current, dtype=float)
+        transmat = np.array(self.model.transmat_, dtype=float)
+        if transmat.shape != (previous.size, previous.size) or current.size != previous.size:
+            return
+        weight = rate * previous[:, None]
+        transmat = (1.0 - weight) * transmat + weight * current[None, :]
+        self.model.transmat_ = transmat / transmat.sum(axis=1, keepdims=True)
+
+    def _infer_last(
+        self,
+        obs_tail: np.ndarray,
+        start_probs: Optional[np.ndarray] = None,
+    ) -> Optional[np.ndarray]:
+        """
+        Raw posterior for the last row of the tail, or None on failure.
 
+        A returned vector always has exactly n_states entries summing to 1
+        (see `_checked_posterior`).
+        """
         try:
             # Forward algorithm → posterior state probabilities for last timestep
-            _, posteriors = self.model.score_samples(obs_norm)
-            raw_probs = posteriors[-1]  # last timestep's state distribution
+            obs_norm = self._standardize(obs_tail)
+            posteriors = self._posteriors(obs_norm, start_probs)
+            last = self._checked_posterior(posteriors[-1])
+            self._filtering_disagreement = self._context_disagreement(obs_norm, start_probs)
+            if self._last_row_norm is not None and np.array_equal(obs_norm[-1], self._last_row_norm):
+                self._stalled_updates += 1
+            else:
+                self._stalled_updates = 0
+            self._last_row_norm = np.array(obs_norm[-1], dtype=float)
+            self._last_raw_posterior = last.copy()
+            if self.cfg.get("HMM_EWMA_COVARIANCE", False):
+                self._update_ewma_covars(obs_norm[-1], last)
+            if (
+                self.cfg.get("HMM_ONLINE_TRANSITIONS", False)
+                and len(posteriors) >= 2
+                and self._stalled_updates == 0
+            ):
+                self._update_online_transitions(posteriors[-2], last)
+            return last  # last timestep's state distribution
         except Exception as e:
             logger.warning("HMM inference failed: %s", e)
-            return self.state
+            return None
+
+    def _model_n_states(self) -> int:
+        """State count of the fitted model (HMM_N_STATES if its transmat is unusable)."""
+        try:
+            n = int(np.asarray(self.model.transmat_).shape[0])
+        except (AttributeError, IndexError, TypeError, ValueError):
+            n = 0
+        return n if n > 0 else max(1, int(self.cfg["HMM_N_STATES"]))
+
+    def _checked_posterior(self, posterior) -> np.ndarray:
+        """
+        Guarantee an n_states-long, finite, normalized raw posterior. A
+        wrong-length or degenerate vector (corrupted model) falls back to
+        the model's start distribution, or uniform, with a warning.
+        """
+        n_states = self._model_n_states()
+
+        def usable(v) -> Optional[np.ndarray]:
+            try:
+                v = np.asarray(v, dtype=float).reshape(-1)
+            except (TypeError, ValueError):
+                return None
+            if v.size != n_states or not np.isfinite(v).all() or (v < 0.0).any() or v.sum() <= 0.0:
+                return None
+            return v
+
+        p = usable(posterior)
+        if p is None:
+            logger.warning(
+                "HMM posterior invalid for %d states (got %r); using fallback",
+                n_states, posterior,
+            )
+            p = usable(getattr(self.model, "startprob_", None))
+            if p is None:
+                p = np.ones(n_states)
+        p = p / p.sum()
+        if p.size != n_states or abs(float(p.sum()) - 1.0) >= 1e-9:
+            raise ValueError(
+                f"posterior guard failed: {p.size} entries for {n_states} states, sum {float(p.sum())!r}"
+            )
+        return p
+
+    def _context_disagreement(
+        self,
+        obs_norm: np.ndarray,
+        start_probs: Optional[np.ndarray] = None,
+    ) -> bool:
+        """
+        True when the last bar's filtered regime differs from the one the
+        window context predicted for it (filtered[T-2] @ A).
+
+        At the final bar the smoothed and filtered posteriors are identical
+        (nothing comes after it), so the context side uses the one-step
+        prediction from the preceding bar instead.
+        """
+        if len(obs_norm) < 2:
+            return False
+        try:
+            if start_probs is None:
+                start_probs = self.model.startprob_
+            transmat = np.asarray(self.model.transmat_, dtype=float)
+            _, filtered, _ = _forward_backward(
+                start_probs, transmat, self._emission_log_densities(obs_norm)
+            )
+        except Exception as e:
+            logger.debug("HMM filtering disagreement skipped: %s", e)
+            return False
+        predicted = filtered[-2] @ transmat
+        return self._remap_probs(predicted).argmax() != self._remap_probs(filtered[-1]).argmax()
+
+    def filtering_disagreement(self) -> bool:
+        """
+        True when the latest bar pulled the regime away from what the
+        preceding window implied — a "wait" signal. Set by each update.
+        """
+        return bool(self._filtering_disagreement)
 
-        # Remap raw HMM state indices to semantic labels
+    def probability_length_mismatch(self, state: RegimeState) -> Optional[str]:
+        """
+        Describe why `state.probabilities` cannot be used by this detector,
+        or None when it can. Emitted probabilities are always in Regime order
+        (one entry per label, whatever HMM_N_STATES is), so a vector of any
+        other length — e.g. raw per-state posteriors from an older build with
+        a different state count — is rejected rather than indexed blindly.
+        """
+        try:
+            size = np.asarray(state.probabilities, dtype=float).reshape(-1).size
+        except (TypeError, ValueError):
+            return f"probabilities {state.probabilities!r} are not numeric"
+        if size != len(Regime):
+            return (
+                f"probabilities have {size} entries, expected {len(Regime)} "
+                f"(HMM_N_STATES={self.cfg['HMM_N_STATES']})"
+            )
+        return None
+
+    def _remap_probs(self, raw_probs: np.ndarray) -> np.ndarray:
+        """
+        Remap raw HMM state indices to semantic labels (Regime order).
+        Several raw states may share a label; their mass is summed. Raw
+        indices past the end of a short vector count as zero.
+        """
+        raw_probs = np.asarray(raw_probs, dtype=float).reshape(-1)
         labeled_probs = np.zeros(3)
         for raw_idx, label in self._state_label_map.items():
-            labeled_probs[label] = raw_probs[raw_idx]
+            if 0 <= int(raw_idx) < raw_probs.size:
+                labeled_probs[label] += raw_probs[int(raw_idx)]
+        return labeled_probs
 
+    def _bias_gain(self, labeled_probs: np.ndarray) -> float:
+        """
+        HMM_BIAS_GAIN for this posterior: a scalar applies to both sides; a
+        [bearish_gain, bullish_gain] pair is picked by the sign of P(bull) - P(bear).
+        """
+        gain = self.cfg["HMM_BIAS_GAIN"]
+        if isinstance(gain, (list, tuple, np.ndarray)):
+            if len(gain) != 2:
+                raise ValueError(
+                    f"HMM_BIAS_GAIN must be a scalar or [bearish, bullish], got {gain!r}"
+                )
+            side = labeled_probs[Regime.BULLISH] - labeled_probs[Regime.BEARISH]
+            return float(gain[1] if side >= 0.0 else gain[0])
+        return float(gain)
+
+    def _round_output(self, value: float) -> float:
+        """Round an emitted scalar to HMM_OUTPUT_PRECISION decimals (None/<0 = raw)."""
+        precision = self.cfg.get("HMM_OUTPUT_PRECISION", 4)
+        if precision is None or int(precision) < 0:
+            return float(value)
+        return round(float(value), int(precision))
+
+    def _emit_state(
+        self,
+        labeled_probs: np.ndarray,
+        observation_count: int,
+        as_of_ts: Optional[float] = None,
+    ) -> RegimeState:
+        """Derive regime/confidence/bias from labeled probabilities and store it."""
+        previous = self.state
+        # Any emission invalidates the fast path; `update` re-keys afterwards.
+        self._last_input_key = None
+        self.state = self._build_state(labeled_probs, observation_count, as_of_ts)
+        factor = self._stall_factor() * self._window_fill_factor(observation_count)
+        if factor < 1.0:
+            self.state.confidence = self._round_output(self.state.confidence * factor)
+        self._apply_min_dwell(previous)
+        self.state.window_capped = bool(self._window_capped)
+        # The default state (never updated) has no regime to change from.
+        self._regime_changed = (
+            previous.last_update_ts > 0.0 and previous.regime != self.state.regime
+        )
+        self._accumulate_confidence_drift(self.state.confidence)
+        logger.info(
+            "HMM regime=%s conf=%.3f bias=%.3f probs=[B:%.2f R:%.2f U:%.2f]",
+            self.state.regime.name, self.state.confidence, self.state.bias_signal,
+            labeled_probs[0], labeled_probs[1], labeled_probs[2],
+        )
+        return self.state
+
+    def _apply_min_dwell(self, previous: RegimeState) -> None:
+        """
+        Hold an emitted regime until it has been emitted for
+        HMM_MIN_DWELL_BARS updates, whatever the argmax says. The first
+        emission is taken as-is (the startup state is no regime to hold) and
+        counts as entering its regime, so a blip out of it is suppressed too.
+        Probabilities, confidence and bias are left as computed; only the
+        label (and `neutralized`, which follows it) is held. A regime restored
+        from a snapshot has no known age (count 0) and is not held.
+        """
+        min_dwell = max(1, int(self.cfg.get("HMM_MIN_DWELL_BARS", 1) or 1))
+        if previous.last_update_ts <= 0.0:
+            self._dwell_updates = 1
+            return
+        if self.state.regime != previous.regime and 0 < self._dwell_updates < min_dwell:
+            self.state.regime = Regime(int(previous.regime))
+            self.state.neutralized = bool(previous.neutralized)
+        if self.state.regime != previous.regime:
+            self._dwell_updates = 1
+        elif self._dwell_updates > 0:
+            self._dwell_updates += 1
+
+    def stalled_updates(self) -> int:
+        """Consecutive updates whose newest observation row repeated the previous one."""
+        return int(self._stalled_updates)
+
+    def _window_fill_factor(self, observation_count: int) -> float:
+        """
+        With HMM_SCALE_CONFIDENCE_BY_WINDOW, the share of the requested
+        inference window the input actually filled: a half-full window's
+        posterior leans on the prior, so its confidence is halved. 1.0 off.
+        """
+        if not self.cfg.get("HMM_SCALE_CONFIDENCE_BY_WINDOW", False) or self._requested_window <= 0:
+            return 1.0
+        return min(1.0, max(0, int(observation_count)) / self._requested_window)
+
+    def _stall_factor(self) -> float:
+        """
+        Confidence multiplier for a frozen feed: falls linearly from 1 to 0
+        over HMM_STALL_DECAY_UPDATES repeated-tail updates, so a stalled
+        feed doesn't read as sustained conviction. 1.0 when disabled.
+        """
+        span = int(self.cfg.get("HMM_STALL_DECAY_UPDATES", 0) or 0)
+        if span <= 0 or self._stalled_updates <= 0:
+            return 1.0
+        return max(0.0, 1.0 - self._stalled_updates / span)
+
+    def _max_abs_bias(self) -> float:
+        """HMM_MAX_ABS_BIAS: session risk cap on |bias_signal|, in [0, 1]."""
+        cap = float(self.cfg.get("HMM_MAX_ABS_BIAS", 1.0))
+        if not np.isfinite(cap) or cap < 0.0:
+            raise ValueError(f"HMM_MAX_ABS_BIAS must be in [0, 1], got {cap!r}")
+        return min(1.0, cap)
+
+    def _build_state(
+        self,
+        labeled_probs: np.ndarray,
+        observation_count: int,
+        as_of_ts: Optional[float] = None,
+    ) -> RegimeState:
+        """RegimeState for labeled probabilities, without storing it."""
         # Determine regime and confidence
         regime = Regime(int(np.argmax(labeled_probs)))
-        sorted_probs = np.sort(labeled_probs)[::-1]
-        confidence = sorted_probs[0] - sorted_probs[1]
+        confidence = self.compute_confidence(
+            labeled_probs, self.cfg.get("HMM_CONFIDENCE_METRIC", "margin")
+        )
 
-        # Compute bias signal: weighted sum of probabilities
-        # BULLISH contributes +1, BEARISH contributes -1, RANGING contributes 0
+        # Compute bias signal (see compute_bias for the HMM_BIAS_MODE forms)
         if confidence < self.cfg["HMM_CONFIDENCE_THRESHOLD"]:
             bias_signal = 0.0  # ambiguous → neutral
         else:
-            bias_signal = (
-                labeled_probs[Regime.BULLISH] - labeled_probs[Regime.BEARISH]
-            ) * self.cfg["HMM_BIAS_GAIN"]
+            bias_signal = self.compute_bias(
+                labeled_probs, self.cfg.get("HMM_BIAS_MODE", "difference")
+            ) * self._bias_gain(labeled_probs)
             bias_signal = max(-1.0, min(1.0, bias_signal))
+            cap = self._max_abs_bias()
+            bias_signal = max(-cap, min(cap, bias_signal))
+
+        # HMM_NEUTRAL_BELOW_THRESHOLD: the label follows the gated bias, so a
+        # weak argmax doesn't read as a directional call.
+        neutralized = bool(
+            self.cfg.get("HMM_NEUTRAL_BELOW_THRESHOLD", False)
+            and confidence < self.cfg["HMM_CONFIDENCE_THRESHOLD"]
+        )
+        if neutralized:
+            regime = Regime.RANGING
 
-        self.state = RegimeState(
+        quality_tier, confidence_modifier = self.training_quality()
+        return RegimeState(
             regime=regime,
             probabilities=labeled_probs.tolist(),
-            confidence=round(confidence, 4),
-            bias_signal=round(bias_signal, 4),
-            last_update_ts=time.time(),
-            observation_count=len(obs_tail),
+            confidence=self._round_output(confidence),
+            peak_probability=float(labeled_probs.max()),
+            bias_signal=self._round_output(bias_signal),
+            last_update_ts=float(time.time() if as_of_ts is None else as_of_ts),
+            observation_count=int(observation_count),
+            quality_tier=quality_tier,
+            confidence_modifier=confidence_modifier,
+            neutralized=neutralized,
         )
 
-        logger.info(
-            "HMM regime=%s conf=%.3f bias=%.3f probs=[B:%.2f R:%.2f U:%.2f]",
-            regime.name, confidence, bias_signal,
-            labeled_probs[0], labeled_probs[1], labeled_probs[2],
-        )
-        return self.state
+    def state_overlap(self, a: int, b: int) -> Optional[float]:
+        """Bhattacharyya coefficient between raw states a and b (None if untrained)."""
+        if not self._trained or self.model is None:
+            return None
+        covars = _model_covars(self.model)
+        means = np.asarray(self.model.means_, dtype=float)
+        return _bhattacharyya_coefficient(means[a], covars[a], means[b], covars[b])
+
+    def emission_separability(self) -> dict[str, float]:
+        """
+        Overlap of adjacent regimes' emission distributions.
+
+        {"bearish_ranging": BC, "ranging_bullish": BC}; values near 1 mean the
+        model can barely tell those regimes apart. Empty when untrained.
+        """
+        if not self._trained or self.model is None:
+            return {}
+        raw_for = {label: raw for raw, label in self._state_label_map.items()}
+        if not all(r in raw_for for r in Regime):
+            return {}
+        return {
+            "bearish_ranging": self.state_overlap(raw_for[Regime.BEARISH], raw_for[Regime.RANGING]),
+            "ranging_bullish": self.state_overlap(raw_for[Regime.RANGING], raw_for[Regime.BULLISH]),
+        }
+
+    def attribution(self) -> list[tuple[str, float]]:
+        """
+        Which features drove the last update's classification.
+
+        For the winning raw state and the runner-up (top two of the last
+        posterior), each feature's contribution is its per-dimension emission
+        log-density difference, log N(x_f | winner) - log N(x_f | runner-up),
+        on the standardized row. Positive values argued for the winner.
+        Full covariances are reduced to their diagonal. Returns
+        (feature_name, contribution) sorted by magnitude, largest first;
+        empty when untrained or before the first update.
+        """
+        if not self._trained or self.model is None:
+            return []
+        if self._last_row_norm is None or self._last_raw_posterior is None:
+            return []
+        posterior = self._last_raw_posterior
+        if posterior.size < 2:
+            return []
+        runner_up, winner = np.argsort(posterior)[-2:]
+        means = np.asarray(self.model.means_, dtype=float)
+        covars = self._effective_covars()
+        if covars.ndim == 3:
+            covars = np.diagonal(covars, axis1=1, axis2=2)
+        x = self._last_row_norm
+
+        def per_feature(k):
+            var = np.maximum(covars[k], 1e-12)
+            return -0.5 * (np.log(2.0 * np.pi * var) + (x - means[k]) ** 2 / var)
+
+        contributions = per_feature(winner) - per_feature(runner_up)
+        pairs = list(zip(self.feature_names(), (float(c) for c in contributions)))
+        return sorted(pairs, key=lambda item: abs(item[1]), reverse=True)
+
+    def feature_informativeness(
+        self, closes: np.ndarray, volumes: np.ndarray
+    ) -> list[tuple[str, float]]:
+        """
+        How much regime information each feature carries on this series.
+
+        Score is the posterior-weighted correlation ratio (eta squared):
+        the share of a feature's variance explained by the inferred state,
+        between-state / total, in [0, 1]. Near 0 means the column looks the
+        same in every state — a candidate to drop. Returns (feature_name,
+        score) sorted high to low; empty when untrained or no rows.
+        """
+        if not self._trained or self.model is None:
+            return []
+        obs = self._with_previous_regime(self.extractor.extract(closes, volumes))
+        if len(obs) == 0:
+            return []
+        return self._informativeness(self._standardize(obs))
+
+    def _informativeness(self, obs_norm: np.ndarray) -> list[tuple[str, float]]:
+        gamma = self._posteriors(obs_norm)
+        weights = gamma.sum(axis=0)
+        grand = obs_norm.mean(axis=0)
+        state_means = (gamma.T @ obs_norm) / np.maximum(weights, 1e-12)[:, None]
+        between = (weights[:, None] * (state_means - grand) ** 2).sum(axis=0)
+        total = ((obs_norm - grand) ** 2).sum(axis=0)
+        scores = np.where(total > 1e-12, between / np.maximum(total, 1e-12), 0.0)
+        pairs = list(zip(self.feature_names(), (float(v) for v in scores)))
+        return sorted(pairs, key=lambda item: item[1], reverse=True)
+
+    def invalidate_model(self) -> None:
+        """
+        Drop the trained model so `update` stops emitting from it until the
+        next successful train. Config, extractor and the last emitted state
+        are kept; `needs_retrain` reports True immediately.
+        """
+        self.model = None
+        self._trained = False
+        self._last_train_ts = 0.0
+        self._grace_start_ts = 0.0  # an explicit invalidate skips the grace period
+        self._reset_ewma_covars()
 
     # --- Stale retrain check -------------------------------------------------
 
     def needs_retrain(self) -> bool:
         """True if model should be retrained (daily by default)."""
+        return self.retrain_reason() in ("never_trained", "stale")
+
+    def retrain_reason(self) -> str:
+        """
+        Why `needs_retrain` says what it does:
+            "never_trained"  untrained and past the grace period → retrain
+            "grace_period"   untrained, but within HMM_RETRAIN_GRACE_SEC of
+                             construction/restore → wait for data
+            "stale"          trained longer than HMM_RETRAIN_INTERVAL_SEC ago
+            "fresh"          trained recently; nothing to do
+        """
+        now = time.time()
         if not self._trained:
-            return True
-        elapsed = time.time() - self._last_train_ts
-        return elapsed >= self.cfg["HMM_RETRAIN_INTERVAL_SEC"]
+            grace = float(self.cfg.get("HMM_RETRAIN_GRACE_SEC", 0.0) or 0.0)
+            if now - self._grace_start_ts < grace:
+                return "grace_period"
+            return "never_trained"
+        if now - self._last_train_ts >= self.cfg["HMM_RETRAIN_INTERVAL_SEC"]:
+            return "stale"
+        return "fresh"
 
     @property
     def transmat(self) -> Optional[list[list[float]]]:
@@ -547,11 +3032,334 @@ class RegimeDetector:
             return None
         return arr.tolist()
 
+    def expected_dwell_bars(self) -> list[float]:
+        """
+        Expected bars spent in each regime before leaving: 1 / (1 - A[i,i]).
+
+        Indexed by Regime (BEARISH, RANGING, BULLISH). An absorbing state
+        reports DWELL_SENTINEL_BARS. Empty when untrained.
+        """
+        matrix = self.transmat
+        if matrix is None or not self._state_label_map:
+            return []
+        diag = np.clip(np.diag(np.asarray(matrix, dtype=float)), 0.0, 1.0)
+        out = [0.0] * len(Regime)
+        for raw_idx, label in self._state_label_map.items():
+            leave = 1.0 - float(diag[int(raw_idx)])
+            if leave <= 1e-12:
+                out[int(label)] = DWELL_SENTINEL_BARS
+            else:
+                out[int(label)] = min(DWELL_SENTINEL_BARS, 1.0 / leave)
+        return out
+
+    def transition_entropy(self) -> list[float]:
+        """
+        Normalized Shannon entropy of each transition row, in [0, 1].
+
+        0 = the next state is certain, 1 = uniform (no persistence learned,
+        expect whipsawing regimes). Indexed by Regime; empty when untrained.
+        """
+        matrix = self.transmat
+        if matrix is None or not self._state_label_map:
+            return []
+        arr = np.clip(np.asarray(matrix, dtype=float), 0.0, None)
+        n_states = arr.shape[1]
+        if n_states < 2:
+            return [0.0] * len(Regime)
+        rows = arr / np.maximum(arr.sum(axis=1, keepdims=True), 1e-300)
+        with np.errstate(divide="ignore", invalid="ignore"):
+            terms = np.where(rows > 0.0, rows * np.log(rows), 0.0)
+        entropy = -terms.sum(axis=1) / np.log(n_states)
+        out = [0.0] * len(Regime)
+        for raw_idx, label in self._state_label_map.items():
+            out[int(label)] = float(np.clip(entropy[int(raw_idx)], 0.0, 1.0))
+        return out
+
+    def simulate(self, length: int, seed: int) -> tuple[list[Regime], np.ndarray]:
+        """
+        Draw a synthetic (regime path, observation rows) pair from the model.
+
+        Walks the Markov chain from the learned start distribution and emits
+        Gaussian rows per state, returned in raw feature units (training
+        standardization undone) so they can be fed back to `train_features`.
+        Fully reproducible for a given seed. Raises ValueError if untrained.
+        """
+        if not self._trained or self.model is None:
+            raise ValueError("simulate requires a trained model")
+        length = int(length)
+        if length < 0:
+            raise ValueError(f"length must be >= 0, got {length}")
+        rng = np.random.default_rng(int(seed))
+        start = np.asarray(self.model.startprob_, dtype=float)
+        transmat = np.asarray(self.model.transmat_, dtype=float)
+        means = np.asarray(self.model.means_, dtype=float)
+        covars = _model_covars(self.model)
+        n_states, n_features = means.shape
+
+        states = np.empty(length, dtype=int)
+        rows = np.empty((length, n_features))
+        state = 0
+        for t in range(length):
+            probs = start if t == 0 else transmat[state]
+            state = int(rng.choice(n_states, p=probs / probs.sum()))
+            states[t] = state
+            if covars.ndim == 2:
+                rows[t] = means[state] + rng.standard_normal(n_features) * np.sqrt(covars[state])
+            else:
+                rows[t] = rng.multivariate_normal(means[state], covars[state])
+
+        rows = rows * self._obs_std + self._obs_mean
+        return [self._state_label_map[int(k)] for k in states], rows
+
+    def export_model(self) -> Optional[dict]:
+        """
+        Read-only export of the fitted parameters as nested lists that
+        `np.array()` ingests directly (e.g. to rebuild the model in hmmlearn
+        for cross-checking). Parameters are in standardized feature space;
+        `obs_mean` / `obs_std` undo it. None when untrained.
+        """
+        if not self._trained or self.model is None:
+            return None
+        means = np.asarray(self.model.means_, dtype=float)
+        return {
+            "n_states": int(means.shape[0]),
+            "n_features": int(means.shape[1]),
+            "covariance_type": str(self.cfg["HMM_COVARIANCE_TYPE"]),
+            "initial_probs": np.asarray(self.model.startprob_, dtype=float).tolist(),
+            "transition_matrix": np.asarray(self.model.transmat_, dtype=float).tolist(),
+            "means": means.tolist(),
+            "covars": np.asarray(self.model.covars_, dtype=float).tolist(),
+            "obs_mean": np.asarray(self._obs_mean, dtype=float).tolist(),
+            "obs_std": np.asarray(self._obs_std, dtype=float).tolist(),
+            "label_map": {int(k): v.name for k, v in self._state_label_map.items()},
+            "feature_names": self.feature_names(),
+        }
+
+    def config_report(self) -> dict:
+        """
+        Which supplied config keys took effect: {"applied": {key: value} for
+        keys the detector reads, "ignored": sorted keys it doesn't know,
+        "effective": the full merged config}. An ignored key is usually a
+        typo whose setting silently fell back to the default.
+        """
+        supplied = self._supplied_config_keys
+        return {
+            "applied": {k: self.cfg[k] for k in supplied if k in self.DEFAULT_CONFIG},
+            "ignored": sorted((k for k in supplied if k not in self.DEFAULT_CONFIG), key=str),
+            "effective": dict(self.cfg),
+        }
+
+    def regime_probabilities_dict(self) -> dict[str, float]:
+        """Current state's probabilities keyed by regime name (see RegimeState.probabilities_dict)."""
+        return self.state.probabilities_dict()
+
+    def summary_line(self, now: Optional[float] = None) -> str:
+        """
+        Stable one-line summary for per-update logs:
+
+            regime=BULLISH conf=0.42 bias=0.18 tier=deep depth=3200 age=12.3s
+
+        Space-separated key=value tokens, always in this order: regime name,
+        confidence and bias to 2 decimals, the state's quality tier, training
+        depth in candles, and seconds since the state was emitted (1 decimal,
+        "age=na" before the first update). `now` defaults to time.time().
+        """
+        st = self.state
+        try:
+            regime = Regime(int(st.regime)).name
+        except (TypeError, ValueError):
+            regime = str(st.regime)
+        if float(st.last_update_ts) > 0.0:
+            now = time.time() if now is None else float(now)
+            age = f"{max(0.0, now - float(st.last_update_ts)):.1f}s"
+        else:
+            age = "na"
+        return (
+            f"regime={regime} conf={float(st.confidence):.2f} bias={float(st.bias_signal):.2f} "
+            f"tier={st.quality_tier} depth={int(self.training_depth)} age={age}"
+        )
+
+    QUALITY_TIERS = ("shallow", "baseline", "deep", "full")
+
+    def health_reasons(self) -> list[str]:
+        """
+        Failing readiness conditions, empty when healthy:
+            "untrained", "stale", "shallow_training:<tier>",
+            "low_separation:<pair>" (1 - overlap below HMM_HEALTH_MIN_SEPARATION).
+        """
+        if not self._trained or self.model is None:
+            return ["untrained"]
+        reasons = []
+        if self.retrain_reason() == "stale":
+            reasons.append("stale")
+        tier, _ = self.training_quality()
+        min_tier = str(self.cfg.get("HMM_HEALTH_MIN_TIER", "baseline"))
+        if min_tier in self.QUALITY_TIERS and (
+            self.QUALITY_TIERS.index(tier) < self.QUALITY_TIERS.index(min_tier)
+        ):
+            reasons.append(f"shallow_training:{tier}")
+        min_sep = float(self.cfg.get("HMM_HEALTH_MIN_SEPARATION", 0.05))
+        for pair, overlap in self.emission_separability().items():
+            if 1.0 - overlap < min_sep:
+                reasons.append(f"low_separation:{pair}")
+        return reasons
+
+    def health(self) -> bool:
+        """Single gate for trusting the bias: trained, fresh, deep enough, separable."""
+        return not self.health_reasons()
+
+    def describe(self) -> dict:
+        """
+        One-call diagnostic dump for bug reports (JSON-serializable).
+
+        Composes config, training metadata, fit log-likelihood, occupancy,
+        transition diagnostics, emission separability and the live state.
+        The tertiary transition lives in bot.py and is not included here.
+        """
+        quality_tier, confidence_modifier = self.training_quality()
+        log_likelihood = None
+        history = getattr(getattr(self.model, "monitor_", None), "history", None)
+        if history:
+            log_likelihood = float(list(history)[-1])
+        return {
+            "config": {k: (v.tolist() if isinstance(v, np.ndarray) else v) for k, v in self.cfg.items()},
+            "extractor": self.extractor.config(),
+            "trained": bool(self._trained),
+            "training_depth": int(self.training_depth),
+            "quality_tier": quality_tier,
+            "confidence_modifier": float(confidence_modifier),
+            "last_train_ts": float(self._last_train_ts),
+            "log_likelihood": log_likelihood,
+            "label_map": {str(k): v.name for k, v in self._state_label_map.items()},
+            "occupancy": self.state_effective_counts(),
+            "transmat": self.transmat,
+            "transition_entropy": self.transition_entropy(),
+            "expected_dwell_bars": self.expected_dwell_bars(),
+            "emission_separability": self.emission_separability(),
+            "state": self.state.to_dict(),
+            "last_update_changed_regime": bool(self._regime_changed),
+        }
+
+
+class GatedDetector:
+    """
+    Two-stage detector: a volatility gate in front of a directional HMM.
+
+    When the rolling std of log returns over `vol_window` bars is below
+    `vol_threshold` the market is "quiet" and the emitted state is forced
+    neutral (RANGING, zero confidence and bias → symmetric grid). The
+    directional detector still updates every tick so it is current when the
+    gate opens; its raw probabilities are kept on the emitted state.
+    """
+
+    def __init__(
+        self,
+        detector: RegimeDetector,
+        vol_window: int = 20,
+        vol_threshold: float = 0.002,
+    ):
+        self.detector = detector
+        self.vol_window = max(2, int(vol_window))
+        self.vol_threshold = max(0.0, float(vol_threshold))
+        self.gate_quiet = False
+        self.state = RegimeState()
+
+    def train(self, closes: np.ndarray, volumes: np.ndarray) -> bool:
+        return self.detector.train(closes, volumes)
+
+    def realized_volatility(self, closes: np.ndarray) -> Optional[float]:
+        """Std of the last `vol_window` log returns, or None if too short."""
+        c = np.asarray(closes, dtype=float)
+        c = c[np.isfinite(c) & (c > 0)]
+        if len(c) < self.vol_window + 1:
+            return None
+        returns = np.diff(np.log(c[-(self.vol_window + 1):]))
+        return float(np.std(returns))
+
+    def is_quiet(self, closes: np.ndarray) -> bool:
+        vol = self.realized_volatility(closes)
+        return vol is not None and vol < self.vol_threshold
+
+    def update(
+        self,
+        closes: np.ndarray,
+        volumes: np.ndarray,
+        as_of_ts: Optional[float] = None,
+    ) -> RegimeState:
+        directional = self.detector.update(closes, volumes, as_of_ts)
+        self.gate_quiet = self.is_quiet(closes)
+        if self.gate_quiet:
+            self.state = replace(
+                directional,
+                regime=Regime.RANGING,
+                confidence=0.0,
+                bias_signal=0.0,
+            )
+        else:
+            self.state = directional
+        return self.state
+
 
 # ---------------------------------------------------------------------------
 # 4. Integration helpers: blending with §15 trend system
 # ---------------------------------------------------------------------------
 
+def resample_closes_volumes(
+    closes: np.ndarray,
+    volumes: np.ndarray,
+    factor: int,
+) -> tuple[np.ndarray, np.ndarray]:
+    """
+    Aggregate a higher-frequency series into bars of `factor` candles.
+
+    close = last close of each bucket, volume = bucket sum. Buckets start at
+    the oldest candle; a trailing partial bucket is dropped so every output
+    bar covers exactly `factor` inputs. bot.py's tertiary bootstrap runs
+    each gap-free stretch of lower-interval candles through it, so the
+    resampled feed aggregates the same way as this module.
+    """
+    factor = int(factor)
+    if factor < 1:
+        raise ValueError(f"factor must be >= 1, got {factor}")
+    closes = np.asarray(closes, dtype=float)
+    volumes = np.asarray(volumes, dtype=float)
+    if len(closes) != len(volumes):
+        raise ValueError("closes and volumes must be same length")
+
+    n_buckets = len(closes) // factor
+    usable = n_buckets * factor
+    if n_buckets == 0:
+        return np.empty(0), np.empty(0)
+    out_closes = closes[:usable].reshape(n_buckets, factor)[:, -1]
+    out_volumes = volumes[:usable].reshape(n_buckets, factor).sum(axis=1)
+    return out_closes.copy(), out_volumes
+
+
+def reshape_probabilities(probs, sharpness: float) -> list[float]:
+    """
+    Reshape a probability vector with softmax(sharpness * ln p), i.e.
+    p_i^s / sum_j p_j^s, without touching the model. s = 1 is a no-op,
+    s > 1 sharpens toward one-hot, 0 <= s < 1 flattens (s = 0 is uniform).
+    Usable on any `RegimeState.probabilities`.
+    """
+    s = float(sharpness)
+    if not np.isfinite(s) or s < 0.0:
+        raise ValueError(f"sharpness must be finite and >= 0, got {sharpness!r}")
+    p = np.asarray(probs, dtype=float).reshape(-1)
+    if p.size == 0:
+        raise ValueError("probs must not be empty")
+    p = np.where(np.isfinite(p), np.clip(p, 0.0, None), 0.0)
+    if float(p.sum()) <= 0.0:
+        raise ValueError("probs must have positive mass")
+    if s == 0.0:
+        return np.full(p.size, 1.0 / p.size).tolist()
+    with np.errstate(divide="ignore"):
+        logits = s * np.log(p)
+    logits = logits - logits.max()
+    weights = np.exp(logits)
+    return (weights / weights.sum()).tolist()
+
+
 def compute_blended_idle_target(
     trend_score: float,
     hmm_bias: float,
@@ -582,6 +3390,9 @@ def compute_blended_idle_target(
 def compute_grid_bias(
     regime_state: RegimeState,
     confidence_threshold: float = 0.15,
+    rsi_zone: Optional[float] = None,
+    rsi_band: float = 0.4,
+    rsi_dampening: float = 0.5,
 ) -> dict:
     """
     Translate regime state into concrete grid-bot actions.
@@ -593,8 +3404,14 @@ def compute_grid_bias(
         "entry_spacing_mult_a": float,  # multiplier for A-side entry_pct
         "entry_spacing_mult_b": float,  # multiplier for B-side entry_pct
         "size_skew_override": float | None,  # if set, overrides rebalancer skew
+        "bull_bear_logodds": float,  # RegimeState.bull_bear_logodds(), for ML consumers
     }
     
+    Mean-reversion guard: when the latest `rsi_zone` feature (-1..+1) is
+    beyond ±`rsi_band` on the side the bias leans (overbought under a long
+    bias, oversold under a short one), the bias magnitude driving the
+    multipliers and skew is scaled by `rsi_dampening`. The mode is kept.
+
     This is ADVISORY — bot.py and the reducer still enforce all invariants.
     The rebalancer design constraints (§14.3) are respected:
         - No market orders
@@ -603,6 +3420,12 @@ def compute_grid_bias(
     """
     bias = regime_state.bias_signal
     conf = regime_state.confidence
+    logodds = regime_state.bull_bear_logodds()
+
+    if rsi_zone is not None and np.isfinite(float(rsi_zone)):
+        stretched = float(rsi_zone) * (1.0 if bias > 0 else -1.0)
+        if bias != 0 and stretched > abs(float(rsi_band)):
+            bias = bias * min(1.0, max(0.0, float(rsi_dampening)))
 
     # Low confidence → stay symmetric, don't fight noise
     if conf < confidence_threshold:
@@ -611,6 +3434,7 @@ def compute_grid_bias(
             "entry_spacing_mult_a": 1.0,
             "entry_spacing_mult_b": 1.0,
             "size_skew_override": None,
+            "bull_bear_logodds": logodds,
         }
 
     # Bullish regime: tighten B-side (buy) entries, widen A-side (sell) entries
@@ -621,6 +3445,7 @@ def compute_grid_bias(
             "entry_spacing_mult_a": 1.0 + abs(bias) * 0.5,  # widen short entries
             "entry_spacing_mult_b": max(0.6, 1.0 - abs(bias) * 0.3),  # tighten long entries
             "size_skew_override": min(0.30, abs(bias) * 0.3),  # positive = favor B-side
+            "bull_bear_logodds": logodds,
         }
 
     # Bearish regime: opposite
@@ -629,6 +3454,72 @@ def compute_grid_bias(
         "entry_spacing_mult_a": max(0.6, 1.0 - abs(bias) * 0.3),  # tighten short entries
         "entry_spacing_mult_b": 1.0 + abs(bias) * 0.5,  # widen long entries
         "size_skew_override": max(-0.30, -abs(bias) * 0.3),  # negative = favor A-side
+        "bull_bear_logodds": logodds,
+    }
+
+
+def compute_grid_bias_table(
+    bias_magnitude: float,
+    confidence: float,
+    confidence_threshold: float = 0.15,
+) -> dict[str, dict]:
+    """
+    `compute_grid_bias` output for a hypothetical state in each regime, keyed
+    by regime name, for callers that cache a lookup table.
+
+    BULLISH / BEARISH use +/- `bias_magnitude` at `confidence`; RANGING has
+    no direction, so it is the symmetric output. The synthesized states
+    carry neutral probabilities, so "bull_bear_logodds" is 0 throughout.
+    """
+    magnitude = abs(float(bias_magnitude))
+    signs = {Regime.BEARISH: -1.0, Regime.RANGING: 0.0, Regime.BULLISH: 1.0}
+    table = {}
+    for regime, sign in signs.items():
+        state = RegimeState(
+            regime=regime,
+            confidence=float(confidence) if sign else 0.0,
+            bias_signal=sign * magnitude,
+        )
+        table[regime.name] = compute_grid_bias(state, confidence_threshold)
+    return table
+
+
+def compute_grid_bias_continuous(
+    regime_state: RegimeState,
+    confidence_threshold: float = 0.15,
+) -> dict:
+    """
+    Continuous counterpart of `compute_grid_bias`: same keys, but the
+    multipliers and skew are smooth functions of the bias signal instead of
+    jumping at a mode boundary.
+
+    Below `confidence_threshold` the bias is faded linearly toward 0 by
+    confidence / threshold rather than cut off. At or above the threshold
+    every value equals `compute_grid_bias`'s for a nonzero bias; "mode" is
+    only a label derived from the sign of the (faded) bias, so a zero bias
+    reads "symmetric" with a 0.0 skew where `compute_grid_bias` falls
+    through to "short_bias" with -0.0.
+    """
+    conf = max(0.0, float(regime_state.confidence))
+    threshold = float(confidence_threshold)
+    fade = 1.0 if threshold <= 0.0 else min(1.0, conf / threshold)
+    bias = float(regime_state.bias_signal) * fade
+    up = max(0.0, bias)
+    down = max(0.0, -bias)
+
+    if bias > 0:
+        mode = "long_bias"
+    elif bias < 0:
+        mode = "short_bias"
+    else:
+        mode = "symmetric"
+    return {
+        "mode": mode,
+        # Each side widens with its opposing bias and tightens with its own.
+        "entry_spacing_mult_a": 1.0 + up * 0.5 - min(0.4, down * 0.3),
+        "entry_spacing_mult_b": 1.0 + down * 0.5 - min(0.4, up * 0.3),
+        "size_skew_override": max(-0.30, min(0.30, bias * 0.3)),
+        "bull_bear_logodds": regime_state.bull_bear_logodds(),
     }
 
 
@@ -644,24 +3535,136 @@ def serialize_for_snapshot(detector: RegimeDetector) -> dict:
         _hmm_regime_state: RegimeState as dict
         _hmm_last_train_ts: float
         _hmm_trained: bool
+        _hmm_extractor_config: FeatureExtractor.config() plus the training
+            standardization (obs_mean / obs_std) when trained
+        _hmm_label_map_override: manual label map (list) or None
+        _hmm_confidence_cusum: confidence drift CUSUM (float)
+        _hmm_n_states: state count of the trained model (config value if untrained)
+        _hmm_hit_rate: rolling_hit_rate window {"outcomes": [bool], "pending": call or None}
     
     Note: the model itself is NOT serialized here. It's retrained on startup
     from price_history. This avoids pickle/joblib fragility.
     """
+    extractor_cfg = detector.extractor.config()
+    obs_mean = getattr(detector, "_obs_mean", None)
+    obs_std = getattr(detector, "_obs_std", None)
+    if obs_mean is not None and obs_std is not None:
+        extractor_cfg["obs_mean"] = [float(x) for x in obs_mean]
+        extractor_cfg["obs_std"] = [float(x) for x in obs_std]
+    n_states = int(detector.cfg["HMM_N_STATES"])
+    if detector._trained and detector.model is not None:
+        n_states = int(np.asarray(detector.model.transmat_).shape[0])
     return {
         "_hmm_regime_state": detector.state.to_dict(),
         "_hmm_last_train_ts": detector._last_train_ts,
         "_hmm_trained": detector._trained,
+        "_hmm_extractor_config": extractor_cfg,
+        "_hmm_label_map_override": detector.label_map_override,
+        "_hmm_confidence_cusum": detector.confidence_drift(),
+        "_hmm_n_states": n_states,
+        "_hmm_hit_rate": {
+            "outcomes": list(detector._hit_outcomes),
+            "pending": list(detector._pending_call) if detector._pending_call else None,
+        },
     }
 
 
 def restore_from_snapshot(detector: RegimeDetector, snapshot: dict):
     """
     Restore regime state from snapshot. Model must be retrained separately.
+
+    Extractor spans from the snapshot are adopted so features stay consistent
+    with what produced the saved state; if they differ from the spans the
+    detector was built with, `detector.extractor_config_mismatch` is set.
+
+    A saved state whose probability vector has the wrong length is handled per
+    HMM_STATE_MISMATCH_POLICY: "repair" resets to the neutral RegimeState and
+    sets `detector.state_probability_mismatch`; "error" raises ValueError.
+
+    A snapshot state count that differs from HMM_N_STATES is adopted into the
+    config, since the saved label map and statistics describe that model and
+    the retrain must reproduce it; `detector.n_states_mismatch` is set so the
+    caller can decide to retrain fresh at the configured count instead.
     """
+    detector.state_probability_mismatch = False
+    detector.n_states_mismatch = False
+    try:
+        saved_n_states = int(snapshot.get("_hmm_n_states", 0) or 0)
+    except (TypeError, ValueError):
+        saved_n_states = 0
+    configured = int(detector.cfg["HMM_N_STATES"])
+    if saved_n_states >= 2 and saved_n_states != configured:
+        logger.warning(
+            "HMM restore: snapshot has %d states but HMM_N_STATES=%d; adopting %d",
+            saved_n_states, configured, saved_n_states,
+        )
+        detector.cfg["HMM_N_STATES"] = saved_n_states
+        detector.n_states_mismatch = True
     if "_hmm_regime_state" in snapshot:
-        detector.state = RegimeState.from_dict(snapshot["_hmm_regime_state"])
+        policy = detector.cfg.get("HMM_STATE_MISMATCH_POLICY", "repair")
+        state = RegimeState.from_dict(snapshot["_hmm_regime_state"], on_mismatch=policy)
+        reason = detector.probability_length_mismatch(state)
+        if reason is not None:
+            if str(policy).strip().lower() == "error":
+                raise ValueError(f"snapshot regime state rejected: {reason}")
+            logger.warning("HMM restore: %s; resetting to neutral state", reason)
+            detector.state_probability_mismatch = True
+            state = RegimeState()
+        detector.state = state
+        detector._last_input_key = None
     detector._last_train_ts = snapshot.get("_hmm_last_train_ts", 0.0)
+    detector._grace_start_ts = time.time()
+    try:
+        cusum = float(snapshot.get("_hmm_confidence_cusum", 0.0) or 0.0)
+        detector._confidence_cusum = max(0.0, cusum) if np.isfinite(cusum) else 0.0
+    except (TypeError, ValueError):
+        detector._confidence_cusum = 0.0
+
+    hit_rate = snapshot.get("_hmm_hit_rate")
+    if isinstance(hit_rate, dict):
+        outcomes = hit_rate.get("outcomes")
+        if isinstance(outcomes, list):
+            window = max(1, int(detector.cfg.get("HMM_HIT_RATE_WINDOW", 200)))
+            detector._hit_outcomes = [bool(x) for x in outcomes][-window:]
+        pending = hit_rate.get("pending")
+        try:
+            direction, length, close = pending
+            detector._pending_call = (int(direction), int(length), float(close))
+        except (TypeError, ValueError):
+            detector._pending_call = None
+
+    override = snapshot.get("_hmm_label_map_override")
+    if isinstance(override, list):
+        try:
+            detector.set_label_map(override)
+        except (TypeError, ValueError) as e:
+            logger.warning("HMM restore: ignoring invalid label map override: %s", e)
+
+    saved = snapshot.get("_hmm_extractor_config")
+    detector.extractor_config_mismatch = False
+    if isinstance(saved, dict):
+        current = detector.extractor.config()
+        saved_spans = {k: saved[k] for k in current if k in saved}
+        if any(current[k] != v for k, v in saved_spans.items()):
+            detector.extractor_config_mismatch = True
+            logger.warning(
+                "HMM restore: snapshot extractor config %s differs from detector %s; "
+                "adopting snapshot spans",
+                saved_spans, current,
+            )
+        try:
+            detector.extractor.apply_config(saved_spans)
+        except ValueError as e:
+            logger.warning("HMM restore: invalid snapshot extractor config: %s", e)
+        obs_mean = saved.get("obs_mean")
+        obs_std = saved.get("obs_std")
+        if (
+            isinstance(obs_mean, list)
+            and isinstance(obs_std, list)
+            and len(obs_mean) == len(obs_std) == detector.n_features
+        ):
+            detector._obs_mean = np.asarray(obs_mean, dtype=float)
+            detector._obs_std = np.asarray(obs_std, dtype=float)
     # _trained stays False until train() succeeds — this is intentional.
     # The bot runs in RANGING/neutral mode until retrain completes.
 
@@ -0,0 +1,86 @@
+"""
+Regenerate tests/fixtures/hmm_golden.json for the end-to-end HMM regression test.
+
+The input series is built with the stdlib PRNG so it does not drift across numpy
+releases; the expected RegimeState comes from a full train() + update() pass.
+Only regenerate after an intentional numeric change, and review the diff.
+
+    python tests/fixtures/make_hmm_golden.py
+"""
+
+from __future__ import annotations
+
+import json
+import os
+import random
+import sys
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+sys.path.insert(0, os.path.dirname(os.path.dirname(HERE)))
+
+FIXTURE_PATH = os.path.join(HERE, "hmm_golden.json")
+CONFIG = {
+    "HMM_MIN_TRAIN_SAMPLES": 500,
+    "HMM_N_ITER": 50,
+    "HMM_RANDOM_SEED": 42,
+}
+
+
+def build_series(n: int = 900, seed: int = 1234) -> tuple[list[float], list[float]]:
+    """Bear -> range -> bull drift with deterministic gaussian noise."""
+    rng = random.Random(seed)
+    closes = [0.15]
+    for i in range(1, n):
+        if i < n // 3:
+            drift = -0.0004
+        elif i < 2 * n // 3:
+            drift = 0.0
+        else:
+            drift = 0.0005
+        closes.append(closes[-1] * (1.0 + drift + rng.gauss(0.0, 0.003)))
+    volumes = [abs(rng.gauss(5000.0, 1000.0)) + 1.0 for _ in range(n)]
+    return [round(c, 10) for c in closes], [round(v, 6) for v in volumes]
+
+
+def run_pipeline(closes: list[float], volumes: list[float], config: dict) -> dict:
+    import numpy as np
+    import hmm_regime_detector as hrd
+
+    detector = hrd.RegimeDetector(config)
+    c = np.asarray(closes, dtype=float)
+    v = np.asarray(volumes, dtype=float)
+    if not detector.train(c, v):
+        raise RuntimeError("golden fixture training failed")
+    state = detector.update(c, v)
+    return {
+        "regime": int(state.regime),
+        "probabilities": [float(x) for x in state.probabilities],
+        "confidence": float(state.confidence),
+        "bias_signal": float(state.bias_signal),
+    }
+
+
+def main() -> None:
+    import numpy as np
+    import hmmlearn
+
+    closes, volumes = build_series()
+    expected = run_pipeline(closes, volumes, CONFIG)
+    payload = {
+        "generated_with": {
+            "numpy": np.__version__,
+            "hmmlearn": hmmlearn.__version__,
+        },
+        "config": CONFIG,
+        "closes": closes,
+        "volumes": volumes,
+        "expected": expected,
+    }
+    with open(FIXTURE_PATH, "w", encoding="utf-8") as fh:
+        json.dump(payload, fh, indent=1)
+        fh.write("\n")
+    print(f"wrote {FIXTURE_PATH}: {expected}")
+
+
+if __name__ == "__main__":
+    main()
@@ -2296,6 +2296,36 @@ class BotEventLogTests(unittest.TestCase):
         self.assertAlmostEqual(float(out[1]["close"]), 1.25)
         self.assertAlmostEqual(float(out[1]["volume"]), 30.0)
 
+    def test_bootstrap_tertiary_candles_resample_through_detector_helper(self):
+        rt = bot.BotRuntime()
+        calls = []
+
+        def resample(closes, volumes, factor):
+            calls.append((list(closes), factor))
+            n = len(closes) // factor * factor
+            return (
+                [closes[i + factor - 1] for i in range(0, n, factor)],
+                [sum(volumes[i:i + factor]) for i in range(0, n, factor)],
+            )
+
+        rt._hmm_module = SimpleNamespace(resample_closes_volumes=resample)
+        # Five contiguous 15m candles, a missing one, then four more.
+        times = [900.0 * i for i in range(1, 6)] + [900.0 * i for i in range(7, 11)]
+        rows = [
+            {"time": t, "open": 1.0, "high": 1.0, "low": 1.0, "close": 1.0 + i / 100.0, "volume": 1.0 + i}
+            for i, t in enumerate(times)
+        ]
+
+        with mock.patch.object(config, "HMM_TERTIARY_INTERVAL_MIN", 60):
+            with mock.patch.object(config, "HMM_SECONDARY_INTERVAL_MIN", 15):
+                with mock.patch.object(rt, "_load_recent_ohlcv_rows", return_value=rows):
+                    closes, volumes = rt._fetch_bootstrap_tertiary_candles(target_candles=10)
+
+        self.assertEqual([len(c) for c, _ in calls], [5, 4])
+        self.assertEqual([f for _, f in calls], [4, 4])
+        self.assertEqual(closes, [1.03, 1.08])
+        self.assertEqual(volumes, [10.0, 30.0])
+
     def test_update_hmm_tertiary_transition_requires_confirmation_candles(self):
         rt = bot.BotRuntime()
         rt._hmm_state_tertiary.update({
@@ -2327,6 +2357,78 @@ class BotEventLogTests(unittest.TestCase):
         self.assertGreaterEqual(int(second["confirmation_count"]), 2)
         self.assertTrue(bool(second["confirmed"]))
 
+    def test_update_hmm_tertiary_transition_confidence_weighted_confirmation(self):
+        def run(confidence):
+            rt = bot.BotRuntime()
+            rt._hmm_state_tertiary.update({
+                "enabled": True,
+                "available": True,
+                "trained": True,
+                "regime": "RANGING",
+                "confidence": confidence,
+                "last_update_ts": 1000.0,
+            })
+            rt._update_hmm_tertiary_transition(1000.0)
+            rt._hmm_state_tertiary.update({"regime": "BULLISH", "last_update_ts": 1100.0})
+            rt._update_hmm_tertiary_transition(1100.0)
+            rt._update_hmm_tertiary_transition(1130.0)  # same bar: no extra evidence
+            rt._update_hmm_tertiary_transition(1165.0)
+            return dict(rt._hmm_tertiary_transition)
+
+        with mock.patch.object(config, "HMM_TERTIARY_INTERVAL_MIN", 1):
+            with mock.patch.object(config, "ACCUM_CONFIRMATION_CONFIDENCE_WEIGHTED", True):
+                with mock.patch.object(config, "ACCUM_CONFIRMATION_EVIDENCE", 1.5):
+                    decisive = run(0.9)
+                    marginal = run(0.3)
+
+        self.assertAlmostEqual(float(decisive["confirmation_evidence"]), 1.8)
+        self.assertTrue(bool(decisive["confirmed"]))
+        self.assertAlmostEqual(float(marginal["confirmation_evidence"]), 0.6)
+        self.assertFalse(bool(marginal["confirmed"]))
+        self.assertEqual(int(marginal["confirmation_count"]), 2)
+
+    def test_tertiary_replay_uses_supplied_timestamps(self):
+        class ReplayDetector:
+            _trained = True
+            _last_train_ts = 0.0
+
+            def __init__(self, regimes):
+                self._regimes = iter(regimes)
+                self.state = None
+
+            def needs_retrain(self):
+                return False
+
+            def update(self, closes, volumes, as_of_ts=None):
+                self.state = SimpleNamespace(
+                    regime=next(self._regimes),
+                    probabilities=[0.1, 0.2, 0.7],
+                    confidence=0.8,
+                    bias_signal=0.0,
+                    observation_count=len(closes),
+                    last_update_ts=time.time() if as_of_ts is None else as_of_ts,
+                )
+                return self.state
+
+        rt = bot.BotRuntime()
+        rt._hmm_detector_tertiary = ReplayDetector([1, 2, 2])
+        rt._hmm_module = None
+        rt._hmm_numpy = SimpleNamespace(asarray=lambda values, dtype=float: list(values))
+        bars = [1_000_000.0, 1_003_600.0, 1_007_200.0]
+        with mock.patch.object(config, "HMM_TERTIARY_ENABLED", True):
+            with mock.patch.object(config, "HMM_TERTIARY_INTERVAL_MIN", 60):
+                with mock.patch.object(
+                    rt, "_fetch_recent_candles", return_value=([0.1] * 30, [1000.0] * 30)
+                ):
+                    for ts in bars:
+                        rt._update_hmm_tertiary(ts)
+
+        transition = dict(rt._hmm_tertiary_transition)
+        self.assertEqual(transition["to_regime"], "BULLISH")
+        self.assertEqual(float(transition["changed_at"]), bars[1])
+        self.assertEqual(float(transition["transition_age_sec"]), 3600.0)
+        self.assertEqual(int(transition["confirmation_count"]), 2)
+
     def test_fetch_training_candles_prefers_supabase_ohlcv(self):
         rt = bot.BotRuntime()
         rows = [
@@ -4181,6 +4283,47 @@ class BotEventLogTests(unittest.TestCase):
         self.assertAlmostEqual(snap["trend_target_locked_until"], 2222.0)
         self.assertAlmostEqual(snap["trend_last_update_ts"], 1234.0)
 
+    def test_hmm_snapshot_round_trips_full_secondary_and_tertiary_payloads(self):
+        rt = bot.BotRuntime()
+        primary, secondary, tertiary = object(), object(), object()
+        payloads = {
+            id(primary): {"_hmm_regime_state": {"regime": 1}, "_hmm_confidence_cusum": 0.1},
+            id(secondary): {
+                "_hmm_regime_state": {"regime": 2},
+                "_hmm_confidence_cusum": 0.4,
+                "_hmm_hit_rate": {"outcomes": [True, False], "pending": None},
+                "_hmm_label_map_override": [2, 0, 1],
+                "_hmm_n_states": 4,
+            },
+            id(tertiary): {
+                "_hmm_regime_state": {"regime": 0},
+                "_hmm_confidence_cusum": 0.7,
+                "_hmm_n_states": 3,
+            },
+        }
+        restored = {}
+        rt._hmm_module = SimpleNamespace(
+            serialize_for_snapshot=lambda det: dict(payloads[id(det)]),
+            restore_from_snapshot=lambda det, snap: restored.__setitem__(id(det), dict(snap)),
+        )
+        rt._hmm_detector = primary
+        rt._hmm_detector_secondary = secondary
+        rt._hmm_detector_tertiary = tertiary
+
+        snap = rt._snapshot_hmm_state()
+
+        self.assertAlmostEqual(snap["_hmm_secondary_confidence_cusum"], 0.4)
+        self.assertEqual(snap["_hmm_secondary_label_map_override"], [2, 0, 1])
+        self.assertEqual(snap["_hmm_secondary_n_states"], 4)
+        self.assertEqual(snap["_hmm_tertiary_n_states"], 3)
+
+        with mock.patch.object(rt, "_refresh_hmm_state_from_detector"):
+            with mock.patch.object(rt, "_update_hmm_tertiary_transition"):
+                rt._restore_hmm_snapshot(snap)
+
+        self.assertEqual(restored[id(secondary)], payloads[id(secondary)])
+        self.assertEqual(restored[id(tertiary)], payloads[id(tertiary)])
+
     def test_global_snapshot_persists_dust_fields(self):
         rt = bot.BotRuntime()
         rt._dust_last_absorbed_usd = 4.56
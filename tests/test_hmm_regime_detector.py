@@ -0,0 +1,2757 @@
+import importlib.util
+import json
+import os
+import types
+import unittest
+from unittest import mock
+
+try:
+    import numpy as np
+    import hmm_regime_detector as hrd
+except Exception as exc:  # pragma: no cover
+    np = None
+    hrd = None
+    _IMPORT_ERROR = exc
+else:  # pragma: no cover
+    _IMPORT_ERROR = None
+
+
+def _make_detector(config=None):
+    # RegimeDetector refuses to construct without hmmlearn; most tests only
+    # exercise the pure-numpy paths, so stub the import guard when missing.
+    with mock.patch.object(hrd, "GaussianHMM", hrd.GaussianHMM or object):
+        return hrd.RegimeDetector(config)
+
+
+def _series(n=160, seed=7):
+    rng = np.random.RandomState(seed)
+    closes = 0.15 * np.cumprod(1.0 + 0.002 * rng.randn(n))
+    volumes = np.abs(1000.0 * rng.randn(n)) + 5000.0
+    return closes, volumes
+
+
+def _fixed_posterior(probs):
+    # score_samples stand-in: every timestep gets the same raw posterior.
+    def score_samples(obs):
+        return 0.0, np.tile(np.asarray(probs, dtype=float), (len(obs), 1))
+    return score_samples
+
+
+def _gaussian_params(spread=1.0, var=1.0, stay=0.9):
+    # Three states separated along ema_spread_pct (feature 1), in regime order.
+    means = np.zeros((3, 4))
+    means[:, 1] = [-spread, 0.0, spread]
+    move = (1.0 - stay) / 2.0
+    return {
+        "startprob_": np.full(3, 1.0 / 3.0),
+        "transmat_": np.full((3, 3), move) + np.eye(3) * (stay - move),
+        "means_": means,
+        "covars_": np.full((3, 4), float(var)),
+        "n_components": 3,
+    }
+
+
+def _forward_backward_score_samples(params):
+    # score_samples stand-in running the module's own forward-backward.
+    def score_samples(obs):
+        log_b = hrd._state_log_densities(obs, params["means_"], params["covars_"])
+        loglik, _, smoothed = hrd._forward_backward(
+            params["startprob_"], params["transmat_"], log_b
+        )
+        return loglik, smoothed
+    return score_samples
+
+
+def _install_fake_model(detector, label_map=None, **params):
+    # Stand-in for a fitted hmmlearn model: only the learned attributes.
+    detector.model = types.SimpleNamespace(**params)
+    detector._obs_mean = np.zeros(4)
+    detector._obs_std = np.ones(4)
+    detector._state_label_map = dict(
+        label_map
+        if label_map is not None
+        else {0: hrd.Regime.BEARISH, 1: hrd.Regime.RANGING, 2: hrd.Regime.BULLISH}
+    )
+    detector._trained = True
+    return detector
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RegimeEnumTests(unittest.TestCase):
+    def test_all_matches_count_and_round_trips(self):
+        regimes = hrd.Regime.all()
+        self.assertEqual(len(regimes), hrd.Regime.count())
+        self.assertEqual(regimes, [hrd.Regime.BEARISH, hrd.Regime.RANGING, hrd.Regime.BULLISH])
+        for regime in regimes:
+            self.assertIs(hrd.Regime.from_str(regime.as_str()), regime)
+        self.assertIs(hrd.Regime.from_str("bullish"), hrd.Regime.BULLISH)
+        with self.assertRaises(ValueError):
+            hrd.Regime.from_str("SIDEWAYS")
+
+    def test_opposite_and_direction(self):
+        R = hrd.Regime
+        self.assertIs(R.BEARISH.opposite(), R.BULLISH)
+        self.assertIs(R.BULLISH.opposite(), R.BEARISH)
+        self.assertIs(R.RANGING.opposite(), R.RANGING)
+        self.assertEqual([r.direction() for r in R.all()], [-1, 0, 1])
+        for regime in R.all():
+            self.assertEqual(regime.opposite().direction(), -regime.direction())
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ConfidenceMetricTests(unittest.TestCase):
+    def test_gini_one_hot_and_uniform(self):
+        compute = hrd.RegimeDetector.compute_confidence
+        self.assertAlmostEqual(compute([0.0, 1.0, 0.0], "gini"), 1.0, places=8)
+        self.assertAlmostEqual(compute([1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0], "gini"), 0.0, places=8)
+
+    def test_gini_penalizes_spread_smoothly(self):
+        compute = hrd.RegimeDetector.compute_confidence
+        peaked = compute([0.8, 0.1, 0.1], "gini")
+        spread = compute([0.6, 0.2, 0.2], "gini")
+        self.assertGreater(peaked, spread)
+        self.assertGreater(spread, 0.0)
+
+    def test_margin_is_default(self):
+        compute = hrd.RegimeDetector.compute_confidence
+        self.assertAlmostEqual(compute([0.6, 0.3, 0.1]), 0.3, places=8)
+        self.assertAlmostEqual(
+            compute([0.6, 0.3, 0.1], "entropy"),
+            1.0 - hrd.RegimeDetector.compute_entropy([0.6, 0.3, 0.1]),
+            places=8,
+        )
+
+    def test_unknown_metric_rejected(self):
+        with self.assertRaises(ValueError):
+            hrd.RegimeDetector.compute_confidence([0.6, 0.3, 0.1], "ginni")
+        with self.assertRaises(ValueError):
+            hrd.RegimeDetector({"HMM_CONFIDENCE_METRIC": "ginni"})
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class FeatureExtractorSetterTests(unittest.TestCase):
+    def test_period_setters_round_trip(self):
+        ext = hrd.FeatureExtractor()
+        ext.set_fast_ema_periods(5)
+        ext.set_slow_ema_periods(34)
+        ext.set_rsi_period(7)
+        ext.set_volume_avg_period(10)
+        self.assertEqual(ext.fast_ema_periods, 5)
+        self.assertEqual(ext.slow_ema_periods, 34)
+        self.assertEqual(ext.rsi_period, 7)
+        self.assertEqual(ext.volume_avg_period, 10)
+
+    def test_period_setters_reject_zero(self):
+        ext = hrd.FeatureExtractor()
+        with self.assertRaises(ValueError):
+            ext.set_fast_ema_periods(0)
+        with self.assertRaises(ValueError):
+            ext.set_macd_signal(-3)
+        with self.assertRaises(ValueError):
+            ext.set_rsi_period(2.5)
+        self.assertEqual(ext.fast_ema_periods, 9)
+        self.assertEqual(ext.macd_signal, 9)
+        self.assertEqual(ext.rsi_period, 14)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class VolumeTransformTests(unittest.TestCase):
+    @staticmethod
+    def _skew(x):
+        x = np.asarray(x, dtype=float)
+        return float(((x - x.mean()) ** 3).mean() / x.std() ** 3)
+
+    def test_log_volume_ratio_is_less_skewed(self):
+        closes, _ = _series(n=600)
+        volumes = np.random.default_rng(5).lognormal(mean=8.0, sigma=1.5, size=600)
+        raw = hrd.FeatureExtractor().extract(closes, volumes)[:, 3]
+        logged = hrd.FeatureExtractor(volume_transform="log").extract(closes, volumes)[:, 3]
+        self.assertLess(abs(self._skew(logged)), abs(self._skew(raw)))
+
+    def test_round_trips_through_config_and_rejects_unknown(self):
+        fx = hrd.FeatureExtractor(volume_transform="log")
+        other = hrd.FeatureExtractor()
+        other.apply_config(fx.config())
+        self.assertEqual(other.volume_transform, "log")
+        with self.assertRaises(ValueError):
+            fx.set_volume_transform("sqrt")
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class VolumeRatioSmoothingTests(unittest.TestCase):
+    def _volume_ratio(self, volumes, span):
+        closes, _ = _series(n=len(volumes))
+        return hrd.FeatureExtractor(volume_ratio_span=span).extract(closes, volumes)[:, 3]
+
+    def test_spike_damped_but_sustained_rise_registers(self):
+        spike = np.full(200, 5000.0)
+        spike[150] *= 20.0
+        self.assertLess(self._volume_ratio(spike, 5).max(), 0.5 * self._volume_ratio(spike, 1).max())
+
+        step = np.full(200, 5000.0)
+        step[150:] *= 3.0
+        self.assertGreater(self._volume_ratio(step, 5).max(), 1.5)
+
+    def test_span_validated_and_configurable(self):
+        with self.assertRaises(ValueError):
+            hrd.FeatureExtractor(volume_ratio_span=0)
+        det = _make_detector({"VOLUME_RATIO_SPAN": 4})
+        self.assertEqual(det.extractor.config()["volume_ratio_span"], 4)
+        self.assertEqual(hrd.FeatureExtractor().volume_ratio_span, 1)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class DetrendTests(unittest.TestCase):
+    def test_detrended_features_neutral_on_linear_uptrend(self):
+        rng = np.random.RandomState(3)
+        n = 400
+        closes = 1.0 + 0.001 * np.arange(n) + 0.0003 * rng.randn(n)
+        volumes = np.full(n, 5000.0)
+
+        raw = hrd.FeatureExtractor().extract(closes, volumes)[-150:]
+        detrended = hrd.FeatureExtractor(detrend_span=50).extract(closes, volumes)[-150:]
+
+        # Raw momentum saturates: RSI pinned overbought, spread firmly positive.
+        self.assertGreater(raw[:, 2].mean(), 0.8)
+        self.assertGreater(raw[:, 1].mean(), 0.0)
+        # Detrended momentum sees only the noise around the trend.
+        self.assertLess(abs(detrended[:, 2].mean()), 0.15)
+        self.assertLess(abs(detrended[:, 1].mean()), 0.2 * raw[:, 1].mean())
+
+    def test_median_volume_baseline_recovers_from_spike(self):
+        n = 130
+        closes = np.linspace(1.0, 1.1, n)
+        volumes = np.full(n, 1000.0)
+        volumes[120] = 100000.0
+
+        ema_rows = hrd.FeatureExtractor().extract(closes, volumes)
+        median_rows = hrd.FeatureExtractor(volume_baseline="median").extract(closes, volumes)
+        # Five bars after the spike the EMA baseline is still inflated.
+        self.assertLess(ema_rows[-5, 3], 0.5)
+        self.assertAlmostEqual(median_rows[-5, 3], 1.0, places=9)
+
+    def test_volume_baseline_rejects_unknown(self):
+        with self.assertRaises(ValueError):
+            hrd.FeatureExtractor(volume_baseline="mean")
+
+    def test_detrend_span_setter_allows_zero(self):
+        ext = hrd.FeatureExtractor(detrend_span=30)
+        ext.set_detrend_span(0)
+        self.assertEqual(ext.detrend_span, 0)
+        with self.assertRaises(ValueError):
+            ext.set_detrend_span(-1)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ExpectedDwellTests(unittest.TestCase):
+    def test_untrained_is_empty(self):
+        self.assertEqual(_make_detector().expected_dwell_bars(), [])
+
+    def test_known_diagonal_remapped_by_label(self):
+        det = _install_fake_model(
+            _make_detector(),
+            label_map={0: hrd.Regime.BULLISH, 1: hrd.Regime.RANGING, 2: hrd.Regime.BEARISH},
+            transmat_=np.array([
+                [0.9, 0.05, 0.05],
+                [0.25, 0.5, 0.25],
+                [0.0, 0.0, 1.0],
+            ]),
+        )
+        dwell = det.expected_dwell_bars()
+        self.assertAlmostEqual(dwell[hrd.Regime.BULLISH], 10.0, places=6)
+        self.assertAlmostEqual(dwell[hrd.Regime.RANGING], 2.0, places=6)
+        self.assertEqual(dwell[hrd.Regime.BEARISH], hrd.DWELL_SENTINEL_BARS)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ForkTests(unittest.TestCase):
+    def test_fork_matches_first_bar_then_diverges(self):
+        det = _install_fake_model(
+            _make_detector(),
+            score_samples=_fixed_posterior([0.1, 0.2, 0.7]),
+        )
+        closes, volumes = _series()
+        det.update(closes, volumes)
+
+        fork = det.fork()
+        self.assertEqual(fork.state.regime, hrd.Regime.RANGING)
+        self.assertEqual(fork._state_label_map, det._state_label_map)
+        self.assertIsNot(fork.model, det.model)
+        self.assertIsNot(fork.extractor, det.extractor)
+
+        a = det.update(closes, volumes)
+        b = fork.update(closes, volumes)
+        self.assertEqual(a.regime, b.regime)
+        self.assertEqual(a.probabilities, b.probabilities)
+        self.assertEqual(a.bias_signal, b.bias_signal)
+
+        fork.model.score_samples = _fixed_posterior([0.8, 0.1, 0.1])
+        fork.update(closes, volumes)
+        self.assertEqual(fork.state.regime, hrd.Regime.BEARISH)
+        self.assertEqual(det.state.regime, hrd.Regime.BULLISH)
+
+
+def _regime_series(n=900, seed=42):
+    # Bear -> range -> bull drift, same shape as the module demo.
+    rng = np.random.RandomState(seed)
+    price = np.zeros(n)
+    price[0] = 0.15
+    for i in range(1, n):
+        drift = -0.0003 if i < n // 3 else (0.0 if i < 2 * n // 3 else 0.0004)
+        price[i] = price[i - 1] * (1 + drift + rng.randn() * 0.003)
+    volume = np.abs(rng.randn(n) * 1000 + 5000)
+    return price, volume
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+@unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+class MinSamplesPerStateTests(unittest.TestCase):
+    def test_starved_states_refuse_training(self):
+        closes, volumes = _regime_series()
+        n_obs = len(hrd.FeatureExtractor().extract(closes, volumes))
+        # No more than one state can hold over half the mass, so at least
+        # two states are starved against this threshold.
+        det = hrd.RegimeDetector({
+            "HMM_MIN_TRAIN_SAMPLES": 200,
+            "HMM_MIN_SAMPLES_PER_STATE": n_obs / 2.0 + 1.0,
+        })
+        self.assertFalse(det.train(closes, volumes))
+        self.assertFalse(det._trained)
+        self.assertIsNone(det.model)
+        counts = det.state_effective_counts()
+        self.assertEqual(len(counts), 3)
+        self.assertAlmostEqual(sum(counts), n_obs, delta=1e-6 * n_obs)
+
+    def test_guard_disabled_by_default(self):
+        closes, volumes = _regime_series()
+        det = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 200})
+        self.assertTrue(det.train(closes, volumes))
+        self.assertTrue(all(c > 0.0 for c in det.state_effective_counts()))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+@unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+class MStepVarianceTests(unittest.TestCase):
+    def test_diag_update_matches_two_pass_variance(self):
+        # hmmlearn's diagonal M-step forms E[x²] - μ²; on the standardized
+        # training rows it must agree with a two-pass weighted variance.
+        closes, volumes = _regime_series()
+        det = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 200})
+        self.assertTrue(det.train(closes, volumes))
+        obs = det._standardize(det.extractor.extract(closes, volumes))
+        posteriors = det.model.predict_proba(obs)
+
+        step = hrd.GaussianHMM(
+            n_components=det.model.n_components,
+            covariance_type="diag",
+            n_iter=1,
+            init_params="",
+        )
+        step.startprob_ = det.model.startprob_
+        step.transmat_ = det.model.transmat_
+        step.means_ = det.model.means_
+        step.covars_ = hrd._model_covars(det.model)
+        step.fit(obs)
+
+        weight = posteriors.sum(axis=0)
+        means = posteriors.T @ obs / weight[:, None]
+        sq_dev = np.stack([
+            posteriors[:, k] @ (obs - means[k]) ** 2 for k in range(len(weight))
+        ])
+        expected = (step.covars_prior + sq_dev) / weight[:, None]
+        np.testing.assert_allclose(step.means_, means, rtol=1e-7, atol=1e-10)
+        np.testing.assert_allclose(hrd._model_covars(step), expected, rtol=1e-7)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+@unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+class ScoreTests(unittest.TestCase):
+    def test_untrained_score_is_none(self):
+        closes, volumes = _regime_series(n=200)
+        self.assertIsNone(hrd.RegimeDetector().score(closes, volumes))
+
+    def test_fit_improves_training_likelihood(self):
+        closes, volumes = _regime_series()
+        barely = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 200, "HMM_N_ITER": 1})
+        fitted = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 200, "HMM_N_ITER": 50})
+        self.assertTrue(barely.train(closes, volumes))
+        self.assertTrue(fitted.train(closes, volumes))
+        self.assertGreater(fitted.score(closes, volumes), barely.score(closes, volumes))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class QualityTierTests(unittest.TestCase):
+    def test_tier_cutoffs(self):
+        tier = hrd.RegimeDetector.compute_quality_tier
+        self.assertEqual(tier(4000, 4000, 500), ("full", 1.00))
+        self.assertEqual(tier(2500, 4000, 500), ("deep", 0.95))
+        self.assertEqual(tier(1000, 4000, 500), ("baseline", 0.85))
+        self.assertEqual(tier(600, 4000, 500), ("shallow", 0.70))
+
+    def test_shallow_depth_propagates_to_emitted_state(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_TRAINING_CANDLES": 4000, "HMM_MIN_TRAIN_SAMPLES": 500}),
+            score_samples=_fixed_posterior([0.1, 0.2, 0.7]),
+        )
+        det.training_depth = 600
+        closes, volumes = _series()
+        state = det.update(closes, volumes)
+        self.assertEqual(state.quality_tier, "shallow")
+        self.assertAlmostEqual(state.confidence_modifier, 0.70)
+
+        det.training_depth = 4000
+        state = det.update(closes, volumes)
+        self.assertEqual(state.quality_tier, "full")
+        self.assertAlmostEqual(state.confidence_modifier, 1.0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ResampleTests(unittest.TestCase):
+    def test_exact_multiple(self):
+        closes = np.arange(1.0, 7.0)          # 1..6
+        volumes = np.full(6, 10.0)
+        c, v = hrd.resample_closes_volumes(closes, volumes, 3)
+        self.assertEqual(c.tolist(), [3.0, 6.0])
+        self.assertEqual(v.tolist(), [30.0, 30.0])
+
+    def test_trailing_partial_bucket_dropped(self):
+        closes = np.arange(1.0, 9.0)          # 1..8
+        volumes = np.arange(1.0, 9.0)
+        c, v = hrd.resample_closes_volumes(closes, volumes, 3)
+        self.assertEqual(c.tolist(), [3.0, 6.0])
+        self.assertEqual(v.tolist(), [6.0, 15.0])
+
+    def test_rejects_bad_factor(self):
+        with self.assertRaises(ValueError):
+            hrd.resample_closes_volumes([1.0], [1.0], 0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class BiasModeTests(unittest.TestCase):
+    def test_modes_on_fixed_distribution(self):
+        bias = hrd.RegimeDetector.compute_bias
+        probs = [0.2, 0.3, 0.5]
+        self.assertAlmostEqual(bias(probs, "difference"), 0.3, places=8)
+        self.assertAlmostEqual(bias(probs, "expected"), 0.3, places=8)
+        # tanh(ln(0.5 / 0.2) / 2) == (0.5 - 0.2) / (0.5 + 0.2)
+        self.assertAlmostEqual(bias(probs, "logodds"), 0.3 / 0.7, places=8)
+
+    def test_expected_extends_to_more_states(self):
+        bias = hrd.RegimeDetector.compute_bias
+        # Directions for 5 ordered states: -1, -0.5, 0, 0.5, 1.
+        self.assertAlmostEqual(bias([0.0, 0.0, 0.0, 1.0, 0.0], "expected"), 0.5, places=8)
+
+    def test_mode_drives_update(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_BIAS_MODE": "logodds"}),
+            score_samples=_fixed_posterior([0.1, 0.2, 0.7]),
+        )
+        closes, volumes = _series()
+        state = det.update(closes, volumes)
+        self.assertAlmostEqual(state.bias_signal, round(0.6 / 0.8, 4), places=8)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RegimeStateNormalizationTests(unittest.TestCase):
+    def test_normalized_vector(self):
+        state = hrd.RegimeState(probabilities=[0.2, 0.3, 0.5])
+        self.assertTrue(state.is_normalized())
+
+    def test_unnormalized_vector_is_repaired(self):
+        state = hrd.RegimeState(probabilities=[2.0, 0.0, 0.0])
+        self.assertFalse(state.is_normalized())
+        state.normalize()
+        self.assertEqual(state.probabilities, [1.0, 0.0, 0.0])
+        self.assertTrue(state.is_normalized())
+
+    def test_update_reports_peak_probability(self):
+        det = _install_fake_model(
+            _make_detector(),
+            score_samples=_fixed_posterior([0.15, 0.25, 0.6]),
+        )
+        closes, volumes = _series()
+        state = det.update(closes, volumes)
+        self.assertEqual(state.peak_probability, max(state.probabilities))
+        self.assertLessEqual(state.confidence, state.peak_probability)
+
+    def test_degenerate_vector_falls_back_to_ranging(self):
+        state = hrd.RegimeState(probabilities=[float("nan"), 0.0, -1.0])
+        self.assertFalse(state.is_normalized())
+        state.normalize()
+        self.assertEqual(state.probabilities, [0.0, 1.0, 0.0])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+@unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+class TrainFeaturesTests(unittest.TestCase):
+    def test_matches_train_on_extractor_output(self):
+        closes, volumes = _regime_series()
+        cfg = {"HMM_MIN_TRAIN_SAMPLES": 200}
+        a = hrd.RegimeDetector(cfg)
+        b = hrd.RegimeDetector(cfg)
+        self.assertTrue(a.train(closes, volumes))
+        self.assertTrue(b.train_features(b.extractor.extract(closes, volumes)))
+
+        np.testing.assert_allclose(a.model.means_, b.model.means_, atol=1e-9)
+        np.testing.assert_allclose(a.model.transmat_, b.model.transmat_, atol=1e-9)
+        self.assertEqual(a._state_label_map, b._state_label_map)
+        self.assertEqual(a.training_depth, len(closes))
+        self.assertEqual(b.training_depth, len(b.extractor.extract(closes, volumes)))
+
+    def test_width_mismatch_raises_and_stays_untrained(self):
+        closes, volumes = _regime_series()
+        det = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 200})
+        rows = det.extractor.extract(closes, volumes)[:, :3]
+        with self.assertRaises(hrd.FeatureCountMismatch) as ctx:
+            det.train_features(rows)
+        self.assertEqual(ctx.exception.expected, 4)
+        self.assertEqual(ctx.exception.actual, 3)
+        self.assertFalse(det._trained)
+        self.assertIsNone(det.model)
+
+    def test_sample_gate_applies(self):
+        closes, volumes = _regime_series(n=300)
+        det = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 500})
+        self.assertFalse(det.train_features(det.extractor.extract(closes, volumes)))
+        self.assertFalse(det._trained)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+@unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+class FitTraceTests(unittest.TestCase):
+    def test_trace_covers_each_iteration_with_rising_likelihood(self):
+        closes, volumes = _regime_series()
+        det = hrd.RegimeDetector(
+            {"HMM_MIN_TRAIN_SAMPLES": 200, "HMM_N_ITER": 6, "HMM_CAPTURE_TRACE": True}
+        )
+        self.assertTrue(det.train(closes, volumes))
+        trace = det.last_fit_trace()
+        logliks = [entry["log_likelihood"] for entry in trace]
+        converged = len(logliks) > 1 and logliks[-1] - logliks[-2] < det.model.tol
+        self.assertTrue(len(trace) == 6 or converged)
+        for prev, cur in zip(logliks, logliks[1:]):
+            self.assertGreaterEqual(cur, prev - 1e-6)
+        np.testing.assert_allclose(trace[-1]["transmat"], det.model.transmat_)
+        np.testing.assert_allclose(trace[-1]["means"], det.model.means_)
+
+    def test_off_by_default_and_capped(self):
+        closes, volumes = _regime_series()
+        det = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 200, "HMM_N_ITER": 6})
+        self.assertTrue(det.train(closes, volumes))
+        self.assertIsNone(det.last_fit_trace())
+
+        det.cfg.update({"HMM_CAPTURE_TRACE": True, "HMM_TRACE_MAX_ITER": 2})
+        self.assertTrue(det.train(closes, volumes))
+        self.assertLessEqual(len(det.last_fit_trace()), 2)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class InferenceCoreTests(unittest.TestCase):
+    def test_forward_backward_last_row_matches_filtered(self):
+        params = _gaussian_params()
+        obs = np.zeros((6, 4))
+        obs[:, 1] = [0.0, 0.2, 0.5, 0.9, 1.1, 1.0]
+        log_b = hrd._state_log_densities(obs, params["means_"], params["covars_"])
+        loglik, filtered, smoothed = hrd._forward_backward(
+            params["startprob_"], params["transmat_"], log_b
+        )
+        np.testing.assert_allclose(filtered[-1], smoothed[-1], atol=1e-12)
+        np.testing.assert_allclose(smoothed.sum(axis=1), np.ones(6), atol=1e-12)
+        self.assertTrue(np.isfinite(loglik))
+
+    def test_full_and_diag_covariances_agree(self):
+        params = _gaussian_params(var=0.5)
+        obs = np.random.RandomState(0).randn(5, 4)
+        full = np.stack([np.diag(v) for v in params["covars_"]])
+        np.testing.assert_allclose(
+            hrd._state_log_densities(obs, params["means_"], params["covars_"]),
+            hrd._state_log_densities(obs, params["means_"], full),
+            atol=1e-10,
+        )
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RobustEmissionTests(unittest.TestCase):
+    def test_huber_softens_far_outlier_only(self):
+        means = np.zeros((1, 4))
+        covars = np.ones((1, 4))
+        near = np.array([[0.5, 0.0, 0.0, 0.0]])
+        far = np.array([[10.0, 0.0, 0.0, 0.0]])
+        gauss_near = hrd._state_log_densities(near, means, covars)[0, 0]
+        huber_near = hrd._state_log_densities(near, means, covars, huber_delta=1.5)[0, 0]
+        gauss_far = hrd._state_log_densities(far, means, covars)[0, 0]
+        huber_far = hrd._state_log_densities(far, means, covars, huber_delta=1.5)[0, 0]
+        self.assertAlmostEqual(gauss_near, huber_near, places=12)
+        self.assertGreater(huber_far, gauss_far)
+        # Linear tail: 1.5 * 10 - 0.5 * 1.5**2 instead of 0.5 * 10**2.
+        self.assertAlmostEqual(huber_far - gauss_far, 50.0 - (15.0 - 1.125), places=9)
+
+    def test_robust_update_runs_without_hmmlearn_posteriors(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_ROBUST_EMISSION": True}),
+            **_gaussian_params(),
+        )
+        closes, volumes = _series()
+        state = det.update(closes, volumes)
+        self.assertTrue(state.is_normalized())
+        self.assertGreater(state.observation_count, 0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class UpdateWithPriorTests(unittest.TestCase):
+    def test_strong_prior_shifts_short_window_posterior(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_INFERENCE_WINDOW": 5}),
+            **_gaussian_params(var=4.0, stay=0.95),
+        )
+        closes, volumes = _series()
+        flat = det.update_with_prior(closes, volumes, [1.0, 1.0, 1.0]).probabilities
+        bullish = det.update_with_prior(closes, volumes, [0.01, 0.01, 0.98]).probabilities
+        self.assertGreater(
+            bullish[hrd.Regime.BULLISH],
+            flat[hrd.Regime.BULLISH] + 0.1,
+        )
+
+    def test_prior_length_validated(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        closes, volumes = _series()
+        with self.assertRaises(ValueError):
+            det.update_with_prior(closes, volumes, [0.5, 0.5])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class LabelMapStabilityTests(unittest.TestCase):
+    def test_first_map_is_not_a_change(self):
+        det = _make_detector()
+        det._install_label_map({0: hrd.Regime.BEARISH, 1: hrd.Regime.RANGING, 2: hrd.Regime.BULLISH})
+        self.assertFalse(det.label_map_changed())
+        self.assertEqual(det.previous_label_map, {})
+
+    def test_inverted_map_reports_change(self):
+        det = _make_detector()
+        det._install_label_map({0: hrd.Regime.BEARISH, 1: hrd.Regime.RANGING, 2: hrd.Regime.BULLISH})
+        det._install_label_map({0: hrd.Regime.BULLISH, 1: hrd.Regime.RANGING, 2: hrd.Regime.BEARISH})
+        self.assertTrue(det.label_map_changed())
+        self.assertEqual(det.previous_label_map[0], hrd.Regime.BEARISH)
+        self.assertEqual(det.label_map[0], hrd.Regime.BULLISH)
+
+    @unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+    def test_retraining_same_data_is_stable(self):
+        closes, volumes = _regime_series()
+        det = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 200})
+        self.assertTrue(det.train(closes, volumes))
+        self.assertTrue(det.train(closes, volumes))
+        self.assertFalse(det.label_map_changed())
+        self.assertEqual(det.previous_label_map, det.label_map)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class SnapshotExtractorConfigTests(unittest.TestCase):
+    def test_spans_round_trip(self):
+        src = _make_detector({"DETREND_SPAN": 40})
+        src.extractor.set_fast_ema_periods(5)
+        src.extractor.set_rsi_period(21)
+        snap = hrd.serialize_for_snapshot(src)
+
+        dst = _make_detector({"DETREND_SPAN": 40})
+        dst.extractor.set_fast_ema_periods(5)
+        dst.extractor.set_rsi_period(21)
+        hrd.restore_from_snapshot(dst, snap)
+        self.assertEqual(dst.extractor.config(), src.extractor.config())
+        self.assertFalse(dst.extractor_config_mismatch)
+
+    def test_mismatch_is_flagged_and_snapshot_spans_adopted(self):
+        src = _make_detector()
+        src.extractor.set_slow_ema_periods(34)
+        snap = hrd.serialize_for_snapshot(src)
+
+        dst = _make_detector()
+        hrd.restore_from_snapshot(dst, snap)
+        self.assertTrue(dst.extractor_config_mismatch)
+        self.assertEqual(dst.extractor.slow_ema_periods, 34)
+
+    def test_standardization_restored(self):
+        src = _install_fake_model(_make_detector(), **_gaussian_params())
+        src._obs_mean = np.array([0.1, 0.2, 0.3, 0.4])
+        src._obs_std = np.array([1.0, 2.0, 3.0, 4.0])
+        dst = _make_detector()
+        hrd.restore_from_snapshot(dst, hrd.serialize_for_snapshot(src))
+        np.testing.assert_allclose(dst._obs_mean, src._obs_mean)
+        np.testing.assert_allclose(dst._obs_std, src._obs_std)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class SnapshotProbabilityLengthTests(unittest.TestCase):
+    def _snapshot(self, probabilities):
+        state = hrd.RegimeState(
+            regime=hrd.Regime.BULLISH, probabilities=probabilities, bias_signal=0.5
+        )
+        return {"_hmm_regime_state": state.to_dict()}
+
+    def test_label_order_state_restores_into_four_state_detector(self):
+        det = _make_detector({"HMM_N_STATES": 4})
+        hrd.restore_from_snapshot(det, self._snapshot([0.1, 0.2, 0.7]))
+        self.assertFalse(det.state_probability_mismatch)
+        self.assertEqual(det.state.regime, hrd.Regime.BULLISH)
+        self.assertEqual(det.state.probabilities, [0.1, 0.2, 0.7])
+
+    def test_raw_length_state_resets_to_neutral_with_flag(self):
+        det = _make_detector({"HMM_N_STATES": 4})
+        snap = self._snapshot([0.1, 0.2, 0.3, 0.4])
+        self.assertIn("4 entries", det.probability_length_mismatch(
+            hrd.RegimeState.from_dict(snap["_hmm_regime_state"])
+        ))
+        with self.assertLogs(hrd.logger, level="WARNING"):
+            hrd.restore_from_snapshot(det, snap)
+        self.assertTrue(det.state_probability_mismatch)
+        self.assertEqual(det.state, hrd.RegimeState())
+        self.assertEqual(hrd.compute_grid_bias(det.state)["mode"], "symmetric")
+
+    def test_error_policy_raises(self):
+        det = _make_detector({"HMM_N_STATES": 4, "HMM_STATE_MISMATCH_POLICY": "error"})
+        with self.assertRaises(ValueError):
+            hrd.restore_from_snapshot(det, self._snapshot([0.25, 0.25, 0.25, 0.25]))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class SnapshotStateCountTests(unittest.TestCase):
+    def test_three_state_snapshot_into_four_state_config(self):
+        src = _install_fake_model(_make_detector(), **_gaussian_params())
+        src.set_label_map([2, 1, 0])
+        snap = hrd.serialize_for_snapshot(src)
+        self.assertEqual(snap["_hmm_n_states"], 3)
+
+        dst = _make_detector({"HMM_N_STATES": 4})
+        with self.assertLogs(hrd.logger, level="WARNING"):
+            hrd.restore_from_snapshot(dst, snap)
+        self.assertTrue(dst.n_states_mismatch)
+        self.assertEqual(dst.cfg["HMM_N_STATES"], 3)
+        self.assertEqual(dst.label_map_override, [2, 1, 0])
+
+    def test_matching_count_is_not_flagged(self):
+        src = _install_fake_model(_make_detector(), **_gaussian_params())
+        dst = _make_detector()
+        hrd.restore_from_snapshot(dst, hrd.serialize_for_snapshot(src))
+        self.assertFalse(dst.n_states_mismatch)
+        self.assertEqual(dst.cfg["HMM_N_STATES"], 3)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class LookaheadTests(unittest.TestCase):
+    def _detector(self, transmat):
+        return _install_fake_model(
+            _make_detector(),
+            score_samples=_fixed_posterior([0.1, 0.1, 0.8]),
+            transmat_=np.asarray(transmat, dtype=float),
+        )
+
+    def test_zero_weight_matches_update(self):
+        det = self._detector(np.full((3, 3), 1.0 / 3.0))
+        closes, volumes = _series()
+        plain = det.update(closes, volumes).probabilities
+        self.assertEqual(det.update_lookahead(closes, volumes, 0.0).probabilities, plain)
+
+    def test_sticky_chain_barely_moves(self):
+        det = self._detector([
+            [0.99, 0.005, 0.005],
+            [0.005, 0.99, 0.005],
+            [0.005, 0.005, 0.99],
+        ])
+        closes, volumes = _series()
+        base = det.update(closes, volumes).probabilities
+        ahead = det.update_lookahead(closes, volumes, 1.0).probabilities
+        self.assertLess(max(abs(a - b) for a, b in zip(base, ahead)), 0.02)
+
+    def test_mean_reverting_chain_pulls_toward_ranging(self):
+        det = self._detector([[0.1, 0.8, 0.1]] * 3)
+        closes, volumes = _series()
+        base = det.update(closes, volumes).probabilities
+        half = det.update_lookahead(closes, volumes, 0.5).probabilities
+        full = det.update_lookahead(closes, volumes, 1.0).probabilities
+        self.assertGreater(half[hrd.Regime.RANGING], base[hrd.Regime.RANGING])
+        self.assertGreater(full[hrd.Regime.RANGING], half[hrd.Regime.RANGING])
+        self.assertEqual(det.update_lookahead(closes, volumes, 5.0).probabilities, full)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class InvalidateModelTests(unittest.TestCase):
+    def test_update_returns_prior_state_after_invalidate(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_INFERENCE_WINDOW": 30}),
+            score_samples=_fixed_posterior([0.1, 0.2, 0.7]),
+        )
+        det._last_train_ts = 1.0e12
+        closes, volumes = _series()
+        before = det.update(closes, volumes)
+        self.assertFalse(det.needs_retrain())
+
+        det.invalidate_model()
+        self.assertIsNone(det.model)
+        self.assertTrue(det.needs_retrain())
+        self.assertEqual(det.cfg["HMM_INFERENCE_WINDOW"], 30)
+        after = det.update(closes, volumes)
+        self.assertIs(after, before)
+        self.assertEqual(after.regime, hrd.Regime.BULLISH)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class EmissionSeparabilityTests(unittest.TestCase):
+    def test_separated_vs_overlapping_states(self):
+        far = _install_fake_model(_make_detector(), **_gaussian_params(spread=8.0, var=1.0))
+        near = _install_fake_model(_make_detector(), **_gaussian_params(spread=0.05, var=1.0))
+        far_sep = far.emission_separability()
+        near_sep = near.emission_separability()
+        self.assertLess(far_sep["bearish_ranging"], 0.01)
+        self.assertLess(far_sep["ranging_bullish"], 0.01)
+        self.assertGreater(near_sep["bearish_ranging"], 0.99)
+        self.assertGreater(near_sep["ranging_bullish"], 0.99)
+
+    def test_identical_states_overlap_fully(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        self.assertAlmostEqual(det.state_overlap(1, 1), 1.0, places=12)
+
+    def test_untrained_is_empty(self):
+        self.assertEqual(_make_detector().emission_separability(), {})
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class AttributionTests(unittest.TestCase):
+    def test_separating_feature_ranks_first(self):
+        # States differ only in ema_spread_pct; the other features tie exactly.
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        self.assertEqual(det.attribution(), [])
+        det.model.score_samples = _fixed_posterior([0.1, 0.2, 0.7])
+        det.update(*_series())
+
+        ranked = det.attribution()
+        self.assertEqual([name for name, _ in ranked][0], "ema_spread_pct")
+        self.assertNotEqual(ranked[0][1], 0.0)
+        self.assertEqual(sorted(name for name, _ in ranked), sorted(det.extractor.feature_names()))
+        for _, contribution in ranked[1:]:
+            self.assertAlmostEqual(contribution, 0.0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class FeatureInformativenessTests(unittest.TestCase):
+    def test_noise_feature_scores_lowest(self):
+        # Three regime blocks shifted on every feature except volume_ratio.
+        rng = np.random.default_rng(11)
+        centers = np.array([[-1.5, -1.5, -1.5, 0.0], [0.0, 0.0, 0.0, 0.0], [1.5, 1.5, 1.5, 0.0]])
+        rows = np.vstack([c + rng.normal(0.0, 0.3, size=(100, 4)) for c in centers])
+        rows[:, 3] = rng.normal(0.0, 1.0, size=len(rows))
+        params = _gaussian_params()
+        params["means_"] = centers
+        params["covars_"] = np.full((3, 4), 0.09)
+        params["covars_"][:, 3] = 1.0
+        det = _install_fake_model(
+            _make_detector(), score_samples=_forward_backward_score_samples(params), **params
+        )
+
+        ranked = det._informativeness(rows)
+        self.assertEqual(ranked[-1][0], "volume_ratio")
+        self.assertLess(ranked[-1][1], 0.05)
+        self.assertGreater(ranked[0][1], 0.8)
+
+    def test_untrained_is_empty(self):
+        self.assertEqual(_make_detector().feature_informativeness(*_series()), [])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class OutputPrecisionTests(unittest.TestCase):
+    PROBS = [0.1234567, 0.2, 0.6765433]
+
+    def _update(self, precision):
+        det = _install_fake_model(
+            _make_detector({"HMM_OUTPUT_PRECISION": precision}),
+            score_samples=_fixed_posterior(self.PROBS),
+        )
+        closes, volumes = _series()
+        return det.update(closes, volumes)
+
+    def test_default_precision_is_four(self):
+        state = self._update(4)
+        self.assertEqual(state.bias_signal, round(self.PROBS[2] - self.PROBS[0], 4))
+        self.assertEqual(state.confidence, round(self.PROBS[2] - self.PROBS[1], 4))
+
+    def test_higher_precision_keeps_digits(self):
+        state = self._update(6)
+        self.assertEqual(state.bias_signal, round(self.PROBS[2] - self.PROBS[0], 6))
+        self.assertNotEqual(state.bias_signal, round(self.PROBS[2] - self.PROBS[0], 4))
+
+    def test_sentinel_disables_rounding(self):
+        for sentinel in (None, -1):
+            state = self._update(sentinel)
+            self.assertAlmostEqual(state.bias_signal, self.PROBS[2] - self.PROBS[0], places=12)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class GatedDetectorTests(unittest.TestCase):
+    def _gated(self):
+        inner = _install_fake_model(
+            _make_detector(),
+            score_samples=_fixed_posterior([0.05, 0.15, 0.8]),
+        )
+        return hrd.GatedDetector(inner, vol_window=20, vol_threshold=0.001)
+
+    def test_quiet_market_forces_symmetric(self):
+        gated = self._gated()
+        n = 160
+        closes = 0.15 * (1.0 + 1e-5 * np.sin(np.arange(n)))
+        volumes = np.full(n, 5000.0)
+        state = gated.update(closes, volumes)
+        self.assertTrue(gated.gate_quiet)
+        self.assertEqual(state.regime, hrd.Regime.RANGING)
+        self.assertEqual(state.bias_signal, 0.0)
+        self.assertEqual(hrd.compute_grid_bias(state)["mode"], "symmetric")
+        # Directional view is still tracked underneath.
+        self.assertEqual(gated.detector.state.regime, hrd.Regime.BULLISH)
+
+    def test_volatile_market_passes_directional_state(self):
+        gated = self._gated()
+        closes, volumes = _series()
+        state = gated.update(closes, volumes)
+        self.assertFalse(gated.gate_quiet)
+        self.assertEqual(state.regime, hrd.Regime.BULLISH)
+        self.assertEqual(hrd.compute_grid_bias(state)["mode"], "long_bias")
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class LabelMapOverrideTests(unittest.TestCase):
+    def test_inverted_override_flips_bias(self):
+        det = _install_fake_model(
+            _make_detector(),
+            score_samples=_fixed_posterior([0.1, 0.2, 0.7]),
+        )
+        closes, volumes = _series()
+        natural = det.update(closes, volumes)
+        det.set_label_map([2, 1, 0])
+        inverted = det.update(closes, volumes)
+        self.assertGreater(natural.bias_signal, 0.0)
+        self.assertAlmostEqual(inverted.bias_signal, -natural.bias_signal, places=8)
+        self.assertEqual(inverted.regime, hrd.Regime.BEARISH)
+
+    def test_invalid_maps_rejected(self):
+        det = _make_detector()
+        for bad in ([0, 1], [0, 1, 3], [0, 0, 1]):
+            with self.assertRaises(ValueError):
+                det.set_label_map(bad)
+        self.assertIsNone(det.label_map_override)
+
+    def test_override_survives_snapshot(self):
+        src = _make_detector()
+        src.set_label_map([1, 2, 0])
+        dst = _make_detector()
+        hrd.restore_from_snapshot(dst, hrd.serialize_for_snapshot(src))
+        self.assertEqual(dst.label_map_override, [1, 2, 0])
+        self.assertEqual(dst.label_map[0], hrd.Regime.RANGING)
+
+        dst.clear_label_map()
+        self.assertIsNone(dst.label_map_override)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ChangePointScoreTests(unittest.TestCase):
+    def test_abrupt_switch_spikes_at_boundary(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params(spread=3.0))
+        window = det.cfg["HMM_CHANGE_POINT_WINDOW"]
+        switch = 60
+        rng = np.random.default_rng(3)
+        obs = rng.normal(0.0, 0.3, size=(120, 4))
+        obs[:switch, 1] += -3.0
+        obs[switch:, 1] += 3.0
+
+        ends = list(range(2 * window, len(obs) + 1))
+        scores = [det._change_point_from_obs(obs[:end]) for end in ends]
+        peak = int(np.argmax(scores))
+
+        # The spike lands when the recent sub-window starts at the switch.
+        self.assertEqual(ends[peak], switch + window)
+        self.assertGreater(scores[peak], 2.5)
+        quiet = [sc for end, sc in zip(ends, scores) if end != switch + window]
+        self.assertLess(max(quiet), 0.5)
+
+    def test_untrained_or_short_returns_none(self):
+        closes, volumes = _series()
+        self.assertIsNone(_make_detector().change_point_score(closes, volumes))
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        self.assertIsNone(det._change_point_from_obs(np.zeros((5, 4))))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class LabelMapTieBreakTests(unittest.TestCase):
+    def test_exact_spread_tie_breaks_on_macd_then_index(self):
+        derive = hrd.RegimeDetector._derive_label_map
+        means = np.zeros((3, 4))
+        means[:, 1] = [0.5, 0.5, -1.0]
+        means[:, 0] = [0.2, -0.2, 0.0]
+        self.assertEqual(
+            derive(means),
+            {2: hrd.Regime.BEARISH, 1: hrd.Regime.RANGING, 0: hrd.Regime.BULLISH},
+        )
+
+        means[:, 0] = 0.0
+        for _ in range(5):
+            self.assertEqual(
+                derive(means.copy()),
+                {2: hrd.Regime.BEARISH, 0: hrd.Regime.RANGING, 1: hrd.Regime.BULLISH},
+            )
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class PriceCleaningTests(unittest.TestCase):
+    def test_error_policy_reports_offending_index(self):
+        closes, volumes = _series()
+        closes[37] = 0.0
+        closes[90] = -1.0
+        fx = hrd.FeatureExtractor(price_cleaning="error")
+        with self.assertRaises(hrd.NonPositivePrice) as ctx:
+            fx.extract(closes, volumes)
+        self.assertEqual(ctx.exception.index, 37)
+
+    def test_ffill_matches_manually_repaired_series(self):
+        closes, volumes = _series()
+        repaired = closes.copy()
+        repaired[37] = repaired[36]
+        repaired[90:92] = repaired[89]
+        repaired[0] = repaired[1]
+        dirty = repaired.copy()
+        dirty[0] = np.nan
+        dirty[37] = 0.0
+        dirty[90:92] = [-1.0, 0.0]
+
+        fx = hrd.FeatureExtractor()
+        obs = fx.extract(dirty, volumes)
+        np.testing.assert_allclose(obs, fx.extract(repaired, volumes))
+        self.assertTrue(np.all(np.isfinite(obs)))
+        self.assertTrue(np.all((obs[:, 2] >= -1.0) & (obs[:, 2] <= 1.0)))
+
+    def test_unknown_policy_rejected(self):
+        with self.assertRaises(ValueError):
+            hrd.FeatureExtractor(price_cleaning="drop")
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ValidateTests(unittest.TestCase):
+    def test_reports_bad_values_and_short_history(self):
+        fx = hrd.FeatureExtractor()
+        closes, volumes = _series(n=20)
+        closes[[3, 7]] = np.nan
+        closes[10] = -0.5
+        volumes[5] = np.inf
+        before = closes.copy()
+
+        report = fx.validate(closes, volumes)
+        self.assertEqual(report["length"], 20)
+        self.assertEqual(report["non_finite_closes"], 2)
+        self.assertEqual(report["non_finite_volumes"], 1)
+        self.assertEqual(report["non_positive_prices"], 1)
+        self.assertEqual(report["warmup_required"], fx.warmup_required())
+        self.assertFalse(report["enough_history"])
+        self.assertFalse(report["ok"])
+        np.testing.assert_array_equal(closes, before)
+
+    def test_clean_series_passes(self):
+        closes, volumes = _series()
+        report = hrd.FeatureExtractor().validate(closes, volumes)
+        self.assertTrue(report["enough_history"])
+        self.assertTrue(report["ok"])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class TransitionEntropyTests(unittest.TestCase):
+    def test_sticky_low_uniform_high(self):
+        sticky = _install_fake_model(_make_detector(), **_gaussian_params(stay=0.98))
+        for value in sticky.transition_entropy():
+            self.assertLess(value, 0.15)
+
+        uniform = _install_fake_model(_make_detector(), **_gaussian_params(stay=1.0 / 3.0))
+        for value in uniform.transition_entropy():
+            self.assertAlmostEqual(value, 1.0, places=9)
+
+    def test_untrained_is_empty(self):
+        self.assertEqual(_make_detector().transition_entropy(), [])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class InferenceWindowSetterTests(unittest.TestCase):
+    def test_update_uses_new_tail_length(self):
+        det = _install_fake_model(
+            _make_detector(), score_samples=_fixed_posterior([0.2, 0.5, 0.3])
+        )
+        model = det.model
+        closes, volumes = _series()
+        self.assertEqual(det.update(closes, volumes).observation_count, 50)
+
+        det.set_inference_window(20)
+        self.assertEqual(det.update(closes, volumes).observation_count, 20)
+        self.assertIs(det.model, model)
+
+    def test_rejects_small_or_fractional_windows(self):
+        det = _make_detector()
+        for bad in (4, 0, 7.5, "x"):
+            with self.assertRaises(ValueError):
+                det.set_inference_window(bad)
+        self.assertEqual(det.cfg["HMM_INFERENCE_WINDOW"], 50)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class WarmupTests(unittest.TestCase):
+    def test_warmup_tracks_largest_span_plus_window(self):
+        det = _make_detector()
+        self.assertEqual(det.extractor.warmup_required(), 26)  # macd_slow
+        self.assertEqual(det.min_history(), 26 + 50)
+
+        det.extractor.set_volume_avg_period(40)
+        det.set_inference_window(30)
+        self.assertEqual(det.min_history(), 40 + 30)
+
+        det.extractor.set_detrend_span(96)
+        self.assertEqual(det.extractor.warmup_required(), 96)
+
+    def test_min_history_yields_full_window(self):
+        det = _make_detector()
+        closes, volumes = _series(n=det.min_history())
+        self.assertGreaterEqual(
+            len(det.extractor.extract(closes, volumes)), det.cfg["HMM_INFERENCE_WINDOW"]
+        )
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RegimeAtTests(unittest.TestCase):
+    def _detector(self):
+        params = _gaussian_params(spread=0.02, var=0.01)
+        return _install_fake_model(
+            _make_detector({"HMM_INFERENCE_WINDOW": 10_000}),
+            score_samples=_forward_backward_score_samples(params),
+            **params,
+        )
+
+    def test_last_index_agrees_with_update(self):
+        det = self._detector()
+        closes, volumes = _series()
+        at_last = det.regime_at(closes, volumes, len(closes) - 1)
+        live = det.update(closes, volumes)
+        self.assertEqual(at_last.regime, live.regime)
+        np.testing.assert_allclose(at_last.probabilities, live.probabilities)
+        self.assertEqual(at_last.confidence, live.confidence)
+        self.assertEqual(at_last.bias_signal, live.bias_signal)
+        self.assertEqual(at_last.observation_count, live.observation_count)
+
+    def test_index_validation_and_state_untouched(self):
+        det = self._detector()
+        closes, volumes = _series()
+        before = det.state
+        det.regime_at(closes, volumes, 100)
+        self.assertIs(det.state, before)
+        for bad in (-1, len(closes), 0):  # 0 is inside the feature warmup
+            with self.assertRaises(ValueError):
+                det.regime_at(closes, volumes, bad)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class AsymmetricBiasGainTests(unittest.TestCase):
+    def _bias(self, gain, probs):
+        det = _install_fake_model(
+            _make_detector({"HMM_BIAS_GAIN": gain, "HMM_OUTPUT_PRECISION": None}),
+            score_samples=_fixed_posterior(probs),
+        )
+        closes, volumes = _series()
+        return det.update(closes, volumes).bias_signal
+
+    def test_pair_scales_each_side(self):
+        bull, bear = [0.1, 0.3, 0.6], [0.6, 0.3, 0.1]
+        self.assertAlmostEqual(self._bias([2.0, 0.5], bull), 0.25)
+        self.assertAlmostEqual(self._bias([2.0, 0.5], bear), -1.0)
+        self.assertAlmostEqual(self._bias(1.5, bull), -self._bias(1.5, bear))
+
+    def test_wrong_length_rejected(self):
+        with self.assertRaises(ValueError):
+            self._bias([1.0, 1.0, 1.0], [0.1, 0.3, 0.6])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class MaxAbsBiasTests(unittest.TestCase):
+    def _bias(self, cap, probs):
+        det = _install_fake_model(
+            _make_detector({"HMM_MAX_ABS_BIAS": cap, "HMM_OUTPUT_PRECISION": None}),
+            score_samples=_fixed_posterior(probs),
+        )
+        closes, volumes = _series()
+        return det.update(closes, volumes).bias_signal
+
+    def test_strong_signal_clamped_weak_passes(self):
+        self.assertAlmostEqual(self._bias(0.3, [0.05, 0.05, 0.9]), 0.3)
+        self.assertAlmostEqual(self._bias(0.3, [0.9, 0.05, 0.05]), -0.3)
+        self.assertAlmostEqual(self._bias(0.3, [0.3, 0.2, 0.5]), 0.2)
+        self.assertAlmostEqual(self._bias(1.0, [0.05, 0.05, 0.9]), 0.85)
+
+    def test_negative_cap_rejected(self):
+        with self.assertRaises(ValueError):
+            self._bias(-0.1, [0.05, 0.05, 0.9])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class NeutralBelowThresholdTests(unittest.TestCase):
+    WEAK_BULL = [0.3, 0.3, 0.4]
+
+    def _state(self, enabled, probs):
+        det = _install_fake_model(
+            _make_detector({"HMM_NEUTRAL_BELOW_THRESHOLD": enabled}),
+            score_samples=_fixed_posterior(probs),
+        )
+        closes, volumes = _series()
+        return det.update(closes, volumes)
+
+    def test_weak_bullish_emits_ranging(self):
+        state = self._state(True, self.WEAK_BULL)
+        self.assertEqual(state.regime, hrd.Regime.RANGING)
+        self.assertTrue(state.neutralized)
+        self.assertEqual(state.bias_signal, 0.0)
+        np.testing.assert_allclose(state.probabilities, self.WEAK_BULL)
+        restored = hrd.RegimeState.from_dict(state.to_dict(), on_mismatch="error")
+        self.assertEqual(restored.regime, hrd.Regime.RANGING)
+
+    def test_off_by_default_and_confident_calls_kept(self):
+        self.assertEqual(self._state(False, self.WEAK_BULL).regime, hrd.Regime.BULLISH)
+        strong = self._state(True, [0.1, 0.1, 0.8])
+        self.assertEqual(strong.regime, hrd.Regime.BULLISH)
+        self.assertFalse(strong.neutralized)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RegimeChangeFlagTests(unittest.TestCase):
+    def test_true_only_on_flip_bar(self):
+        probs = {"value": [0.1, 0.7, 0.2]}
+
+        def score_samples(obs):
+            return _fixed_posterior(probs["value"])(obs)
+
+        det = _install_fake_model(_make_detector(), score_samples=score_samples)
+        closes, volumes = _series()
+        sequence = [
+            ([0.1, 0.7, 0.2], False),  # first update: nothing to change from
+            ([0.1, 0.6, 0.3], False),
+            ([0.1, 0.3, 0.6], True),
+            ([0.1, 0.2, 0.7], False),
+            ([0.7, 0.2, 0.1], True),
+        ]
+        for p, expected in sequence:
+            probs["value"] = p
+            det.update(closes, volumes)
+            self.assertIs(det.last_update_changed_regime(), expected, p)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class SimulateTests(unittest.TestCase):
+    def _source(self):
+        return _install_fake_model(
+            _make_detector(), **_gaussian_params(spread=4.0, var=0.25, stay=0.97)
+        )
+
+    def test_seed_reproducible(self):
+        det = self._source()
+        path_a, rows_a = det.simulate(200, seed=9)
+        path_b, rows_b = det.simulate(200, seed=9)
+        self.assertEqual(path_a, path_b)
+        np.testing.assert_array_equal(rows_a, rows_b)
+        self.assertEqual(rows_a.shape, (200, 4))
+        self.assertTrue(all(isinstance(r, hrd.Regime) for r in path_a))
+        with self.assertRaises(ValueError):
+            _make_detector().simulate(10, seed=1)
+
+    @unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+    def test_refit_recovers_generating_means(self):
+        source = self._source()
+        _, rows = source.simulate(3000, seed=11)
+        refit = _make_detector({"HMM_MIN_TRAIN_SAMPLES": 100})
+        self.assertTrue(refit.train_features(rows))
+
+        recovered = refit.model.means_ * refit._obs_std + refit._obs_mean
+        by_regime = {label: recovered[raw] for raw, label in refit.label_map.items()}
+        for regime, expected in zip(hrd.Regime, source.model.means_):
+            np.testing.assert_allclose(by_regime[regime], expected, atol=0.25)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class StateConsistencyTests(unittest.TestCase):
+    def _snapshot(self):
+        state = hrd.RegimeState(
+            regime=hrd.Regime.BULLISH, probabilities=[0.9, 0.1, 0.0], last_update_ts=1.0
+        )
+        return {"_hmm_regime_state": state.to_dict()}
+
+    def test_mismatched_snapshot_repaired_to_argmax(self):
+        det = _make_detector()
+        hrd.restore_from_snapshot(det, self._snapshot())
+        self.assertEqual(det.state.regime, hrd.Regime.BEARISH)
+        self.assertEqual(det.state.probabilities, [0.9, 0.1, 0.0])
+
+    def test_error_policy_raises(self):
+        det = _make_detector({"HMM_STATE_MISMATCH_POLICY": "error"})
+        with self.assertRaises(ValueError):
+            hrd.restore_from_snapshot(det, self._snapshot())
+
+    def test_consistent_and_tied_states_untouched(self):
+        tied = hrd.RegimeState(regime=hrd.Regime.BULLISH, probabilities=[0.4, 0.2, 0.4])
+        restored = hrd.RegimeState.from_dict(tied.to_dict(), on_mismatch="error")
+        self.assertEqual(restored.regime, hrd.Regime.BULLISH)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class EwmaCovarianceTests(unittest.TestCase):
+    def test_covariances_track_volatility_increase(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_EWMA_COVARIANCE": True, "HMM_EWMA_DECAY": 0.05}),
+            **_gaussian_params(spread=6.0, var=1.0, stay=0.95),
+        )
+        rng = np.random.default_rng(5)
+        calm = rng.normal(0.0, 1.0, size=(300, 4))
+        wild = rng.normal(0.0, 3.0, size=(300, 4))
+        wild[:, 1] = rng.normal(0.0, 1.0, size=300)  # keep the ranging state dominant
+        stream = np.vstack([calm, wild])
+
+        for t in range(1, 301):
+            det._infer_last(stream[max(0, t - 50):t])
+        calm_var = det._effective_covars()[1].copy()
+        for t in range(301, 601):
+            det._infer_last(stream[t - 50:t])
+        wild_var = det._effective_covars()[1]
+
+        self.assertLess(calm_var[0], 2.0)
+        self.assertGreater(wild_var[0], 5.0)
+        np.testing.assert_allclose(det.model.covars_[1], 1.0)  # fitted params untouched
+
+    def test_disabled_uses_fitted_covariances(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params(var=2.0))
+        np.testing.assert_allclose(det._effective_covars(), 2.0)
+        self.assertFalse(det._custom_emissions())
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class DescribeTests(unittest.TestCase):
+    def test_trained_detector_dump(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params(stay=0.95))
+        det.training_depth = 1200
+        det.model.monitor_ = types.SimpleNamespace(history=[-5400.0, -5321.5])
+        info = det.describe()
+
+        for key in (
+            "config", "trained", "training_depth", "quality_tier", "log_likelihood",
+            "occupancy", "transition_entropy", "emission_separability", "state",
+        ):
+            self.assertIn(key, info)
+        self.assertIs(info["trained"], True)
+        self.assertEqual(info["training_depth"], 1200)
+        self.assertEqual(info["log_likelihood"], -5321.5)
+        self.assertEqual(len(info["transition_entropy"]), 3)
+        self.assertTrue(all(isinstance(v, float) for v in info["transition_entropy"]))
+        self.assertTrue(all(isinstance(v, float) for v in info["emission_separability"].values()))
+        self.assertEqual(info["label_map"], {"0": "BEARISH", "1": "RANGING", "2": "BULLISH"})
+        json.dumps(info)
+
+    def test_untrained_dump(self):
+        info = _make_detector().describe()
+        self.assertIs(info["trained"], False)
+        self.assertIsNone(info["log_likelihood"])
+        self.assertEqual(info["transition_entropy"], [])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ConfidenceDriftTests(unittest.TestCase):
+    def _feed(self, det, probs, bars):
+        closes, volumes = _series()
+        det.model.score_samples = _fixed_posterior(probs)
+        for _ in range(bars):
+            det.update(closes, volumes)
+
+    def test_sustained_drop_trips_threshold(self):
+        det = _install_fake_model(_make_detector())
+        self._feed(det, [0.1, 0.2, 0.7], 30)  # margin 0.5
+        self.assertAlmostEqual(det.confidence_drift(), 0.0)
+        self.assertFalse(det.confidence_drift_alarm())
+
+        self._feed(det, [0.3, 0.4, 0.3], 20)  # margin 0.1 → +0.2 per bar
+        self.assertAlmostEqual(det.confidence_drift(), 4.0, places=6)
+        self.assertTrue(det.confidence_drift_alarm())
+
+        self._feed(det, [0.05, 0.05, 0.9], 10)  # recovery pulls it back to 0
+        self.assertAlmostEqual(det.confidence_drift(), 0.0)
+
+    def test_survives_snapshot(self):
+        src = _install_fake_model(_make_detector())
+        self._feed(src, [0.3, 0.4, 0.3], 5)
+        dst = _make_detector()
+        hrd.restore_from_snapshot(dst, hrd.serialize_for_snapshot(src))
+        self.assertAlmostEqual(dst.confidence_drift(), src.confidence_drift())
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ExtractTailTests(unittest.TestCase):
+    def test_tail_matches_full_suffix(self):
+        closes, volumes = _series(n=2000)
+        extractors = (
+            hrd.FeatureExtractor(),
+            hrd.FeatureExtractor(detrend_span=60, volume_baseline="median"),
+        )
+        for fx in extractors:
+            full = fx.extract(closes, volumes)
+            for tail_len in (1, 50, 300):
+                np.testing.assert_allclose(
+                    fx.extract_tail(closes, volumes, tail_len),
+                    full[-tail_len:],
+                    rtol=1e-7, atol=1e-7,
+                )
+
+    def test_short_series_and_empty_tail(self):
+        closes, volumes = _series(n=80)
+        fx = hrd.FeatureExtractor()
+        np.testing.assert_array_equal(
+            fx.extract_tail(closes, volumes, 1000), fx.extract(closes, volumes)
+        )
+        self.assertEqual(fx.extract_tail(closes, volumes, 0).shape, (0, 4))
+        enriched = hrd.FeatureExtractor(enriched_features_enabled=True)
+        self.assertEqual(
+            enriched.extract_tail(closes, volumes, 0).shape[1],
+            enriched.extract(closes, volumes).shape[1],
+        )
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class LogOddsTests(unittest.TestCase):
+    def test_symmetric_and_extreme(self):
+        State = hrd.RegimeState
+        self.assertEqual(State(probabilities=[0.3, 0.4, 0.3]).bull_bear_logodds(), 0.0)
+        self.assertAlmostEqual(
+            State(probabilities=[0.2, 0.2, 0.6]).bull_bear_logodds(), np.log(3.0)
+        )
+        bull = State(probabilities=[0.0, 0.0, 1.0]).bull_bear_logodds()
+        bear = State(probabilities=[1.0, 0.0, 0.0]).bull_bear_logodds()
+        self.assertAlmostEqual(bull, -np.log(State.LOGODDS_EPS))
+        self.assertAlmostEqual(bear, -bull)
+        self.assertEqual(State(probabilities=[0.5, 0.5]).bull_bear_logodds(), 0.0)
+
+    def test_effective_states(self):
+        State = hrd.RegimeState
+        self.assertAlmostEqual(State(probabilities=[0.0, 0.0, 1.0]).effective_states(), 1.0)
+        self.assertAlmostEqual(State(probabilities=[1 / 3, 1 / 3, 1 / 3]).effective_states(), 3.0)
+        self.assertAlmostEqual(State(probabilities=[0.5, 0.5, 0.0]).effective_states(), 2.0)
+        self.assertAlmostEqual(State(probabilities=[0.25] * 4).effective_states(), 4.0)
+
+    def test_probabilities_dict(self):
+        state = hrd.RegimeState(probabilities=[0.2, 0.3, 0.5])
+        named = state.probabilities_dict()
+        self.assertEqual(list(named), ["BEARISH", "RANGING", "BULLISH"])
+        self.assertEqual(list(named.values()), state.probabilities)
+        self.assertAlmostEqual(sum(named.values()), 1.0)
+        det = _make_detector()
+        det.state = state
+        self.assertEqual(det.regime_probabilities_dict(), named)
+        with self.assertRaises(ValueError):
+            hrd.RegimeState(probabilities=[0.5, 0.5]).probabilities_dict()
+
+    def test_runner_up(self):
+        State = hrd.RegimeState
+        self.assertEqual(State(probabilities=[0.5, 0.3, 0.2]).runner_up(), (hrd.Regime.RANGING, 0.3))
+        self.assertEqual(State(probabilities=[0.1, 0.2, 0.7]).runner_up(), (hrd.Regime.RANGING, 0.2))
+        tied = State(probabilities=[0.4, 0.4, 0.2])
+        self.assertEqual(int(np.argmax(tied.probabilities)), hrd.Regime.BEARISH)
+        self.assertEqual(tied.runner_up(), (hrd.Regime.RANGING, 0.4))
+        with self.assertRaises(ValueError):
+            State(probabilities=[1.0]).runner_up()
+
+    def test_grid_bias_carries_logodds(self):
+        state = hrd.RegimeState(probabilities=[0.1, 0.2, 0.7], confidence=0.5, bias_signal=0.6)
+        grid = hrd.compute_grid_bias(state)
+        self.assertAlmostEqual(grid["bull_bear_logodds"], np.log(7.0))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RsiDampeningTests(unittest.TestCase):
+    def test_overbought_rsi_tempers_long_bias(self):
+        state = hrd.RegimeState(confidence=0.5, bias_signal=0.8)
+        neutral = hrd.compute_grid_bias(state, rsi_zone=0.0)
+        overbought = hrd.compute_grid_bias(state, rsi_zone=0.9, rsi_dampening=0.5)
+        self.assertEqual(overbought["mode"], "long_bias")
+        self.assertAlmostEqual(overbought["size_skew_override"], neutral["size_skew_override"] / 2)
+        self.assertLess(overbought["entry_spacing_mult_a"], neutral["entry_spacing_mult_a"])
+        self.assertEqual(neutral, hrd.compute_grid_bias(state))
+
+    def test_only_the_side_the_bias_leans(self):
+        long_state = hrd.RegimeState(confidence=0.5, bias_signal=0.8)
+        short_state = hrd.RegimeState(confidence=0.5, bias_signal=-0.8)
+        self.assertEqual(
+            hrd.compute_grid_bias(long_state, rsi_zone=-0.9), hrd.compute_grid_bias(long_state)
+        )
+        self.assertGreater(
+            hrd.compute_grid_bias(short_state, rsi_zone=-0.9)["size_skew_override"],
+            hrd.compute_grid_bias(short_state)["size_skew_override"],
+        )
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class GridBiasTableTests(unittest.TestCase):
+    def test_long_and_short_entries_mirror(self):
+        table = hrd.compute_grid_bias_table(0.6, confidence=0.5)
+        self.assertEqual(set(table), {"BEARISH", "RANGING", "BULLISH"})
+        bull, bear = table["BULLISH"], table["BEARISH"]
+        self.assertEqual(bull["mode"], "long_bias")
+        self.assertEqual(bear["mode"], "short_bias")
+        self.assertEqual(table["RANGING"]["mode"], "symmetric")
+        self.assertAlmostEqual(bull["entry_spacing_mult_a"], bear["entry_spacing_mult_b"])
+        self.assertAlmostEqual(bull["entry_spacing_mult_b"], bear["entry_spacing_mult_a"])
+        self.assertAlmostEqual(bull["size_skew_override"], -bear["size_skew_override"])
+
+    def test_below_threshold_all_symmetric(self):
+        table = hrd.compute_grid_bias_table(0.6, confidence=0.05)
+        self.assertEqual({entry["mode"] for entry in table.values()}, {"symmetric"})
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ContinuousGridBiasTests(unittest.TestCase):
+    KEYS = ("entry_spacing_mult_a", "entry_spacing_mult_b", "size_skew_override")
+
+    def _grid(self, bias, confidence=0.5):
+        state = hrd.RegimeState(confidence=confidence, bias_signal=bias)
+        return hrd.compute_grid_bias_continuous(state)
+
+    def test_continuous_across_zero_bias(self):
+        neutral = self._grid(0.0)
+        self.assertEqual(neutral["mode"], "symmetric")
+        for eps in (1e-6, -1e-6):
+            nearby = self._grid(eps)
+            for key in self.KEYS:
+                self.assertAlmostEqual(nearby[key], neutral[key], places=5)
+
+    def test_matches_discrete_formulas_at_extremes(self):
+        for bias in (1.0, -1.0, 0.4, -0.4):
+            state = hrd.RegimeState(confidence=0.5, bias_signal=bias)
+            discrete = hrd.compute_grid_bias(state)
+            continuous = hrd.compute_grid_bias_continuous(state)
+            self.assertEqual(continuous["mode"], discrete["mode"])
+            for key in self.KEYS:
+                self.assertAlmostEqual(continuous[key], discrete[key])
+
+    def test_low_confidence_fades_instead_of_cutting_off(self):
+        half = self._grid(0.8, confidence=0.075)
+        full = self._grid(0.4, confidence=0.5)
+        for key in self.KEYS:
+            self.assertAlmostEqual(half[key], full[key])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ConstantColumnTests(unittest.TestCase):
+    def _rows(self):
+        rng = np.random.default_rng(4)
+        rows = rng.normal(0.0, 0.2, size=(900, 4))
+        rows[:, 1] += np.repeat([-2.0, 0.0, 2.0], 300)
+        rows[:, 3] = 1.0  # volume_ratio from a feed reporting constant volume
+        return rows
+
+    def test_constant_column_gets_seeded_jitter(self):
+        det = _make_detector()
+        rows = self._rows()
+        _, std_a, norm_a = det._standardize_training(rows)
+        _, _, norm_b = det._standardize_training(rows)
+        self.assertEqual(std_a[3], 1.0)
+        self.assertGreater(norm_a[:, 3].std(), 0.0)
+        np.testing.assert_array_equal(norm_a, norm_b)
+
+    @unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+    def test_fit_separates_informative_feature(self):
+        det = _make_detector({"HMM_MIN_TRAIN_SAMPLES": 100})
+        self.assertTrue(det.train_features(self._rows()))
+        spread_means = np.sort(det.model.means_[:, 1])
+        self.assertTrue(np.all(np.diff(spread_means) > 0.5))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class FilteringDisagreementTests(unittest.TestCase):
+    def _detector(self):
+        params = _gaussian_params(spread=3.0, var=1.0, stay=0.95)
+        return _install_fake_model(
+            _make_detector(), score_samples=_forward_backward_score_samples(params), **params
+        )
+
+    def test_contradicting_last_bar_flagged(self):
+        det = self._detector()
+        rows = np.zeros((30, 4))
+        rows[:, 1] = -3.0
+        det._infer_last(rows)
+        self.assertFalse(det.filtering_disagreement())
+
+        rows[-1, 1] = 3.0
+        raw = det._infer_last(rows)
+        self.assertTrue(det.filtering_disagreement())
+        self.assertEqual(int(np.argmax(raw)), 2)
+
+    def test_fixed_posterior_model_never_flags(self):
+        det = _install_fake_model(
+            _make_detector(), score_samples=_fixed_posterior([0.1, 0.2, 0.7])
+        )
+        closes, volumes = _series()
+        det.update(closes, volumes)
+        self.assertFalse(det.filtering_disagreement())
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RetrainReasonTests(unittest.TestCase):
+    def test_never_trained(self):
+        det = _make_detector()
+        self.assertEqual(det.retrain_reason(), "never_trained")
+        self.assertTrue(det.needs_retrain())
+
+    def test_grace_period_defers_fresh_detector(self):
+        det = _make_detector({"HMM_RETRAIN_GRACE_SEC": 600.0})
+        self.assertEqual(det.retrain_reason(), "grace_period")
+        self.assertFalse(det.needs_retrain())
+
+        hrd.restore_from_snapshot(det, {"_hmm_last_train_ts": 0.0})
+        self.assertFalse(det.needs_retrain())
+
+        det._grace_start_ts -= 601.0
+        self.assertEqual(det.retrain_reason(), "never_trained")
+        self.assertTrue(det.needs_retrain())
+
+    def test_stale_and_fresh(self):
+        det = _install_fake_model(_make_detector({"HMM_RETRAIN_GRACE_SEC": 600.0}))
+        det._last_train_ts = hrd.time.time()
+        self.assertEqual(det.retrain_reason(), "fresh")
+        self.assertFalse(det.needs_retrain())
+
+        det._last_train_ts -= det.cfg["HMM_RETRAIN_INTERVAL_SEC"] + 1.0
+        self.assertEqual(det.retrain_reason(), "stale")
+        self.assertTrue(det.needs_retrain())
+
+        det.invalidate_model()
+        self.assertEqual(det.retrain_reason(), "never_trained")
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ExportModelTests(unittest.TestCase):
+    def test_shapes_match_model(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        out = det.export_model()
+        k, f = out["n_states"], out["n_features"]
+        self.assertEqual((k, f), (3, 4))
+        self.assertEqual(np.array(out["initial_probs"]).shape, (k,))
+        self.assertEqual(np.array(out["transition_matrix"]).shape, (k, k))
+        self.assertEqual(np.array(out["means"]).shape, (k, f))
+        self.assertEqual(np.array(out["covars"]).shape, (k, f))
+        self.assertEqual(len(out["obs_mean"]), f)
+        self.assertEqual(out["feature_names"], list(hrd.FeatureExtractor.FEATURE_NAMES))
+        self.assertEqual(out["label_map"], {0: "BEARISH", 1: "RANGING", 2: "BULLISH"})
+        self.assertFalse(any(key.startswith("_hmm_") for key in out))
+
+    def test_untrained_returns_none(self):
+        self.assertIsNone(_make_detector().export_model())
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class BoundedHistoryTests(unittest.TestCase):
+    def _detector(self, config):
+        params = _gaussian_params(spread=0.002, var=0.01)
+        return _install_fake_model(
+            _make_detector(config), score_samples=_forward_backward_score_samples(params), **params
+        )
+
+    def test_bounded_update_matches_unbounded(self):
+        closes, volumes = _series(n=5000)
+        bounded = self._detector({"HMM_BOUNDED_HISTORY": True})
+        unbounded = self._detector(None)
+        self.assertLess(bounded.max_history(), len(closes))
+
+        trimmed = bounded.update(closes, volumes)
+        full = unbounded.update(closes, volumes)
+        np.testing.assert_allclose(trimmed.probabilities, full.probabilities)
+        self.assertEqual(trimmed.bias_signal, full.bias_signal)
+        self.assertEqual(bounded.last_observation, unbounded.last_observation)
+
+    def test_cap_never_below_min_history_and_off_by_default(self):
+        det = self._detector({"HMM_BOUNDED_HISTORY": True, "HMM_MAX_HISTORY": 10})
+        fx = det.extractor
+        floor = 50 + fx.rsi_period + fx.TAIL_WARMUP_SPANS * fx.warmup_required()
+        self.assertEqual(det.max_history(), floor)
+        self.assertGreater(det.max_history(), det.min_history())
+        det = self._detector({"HMM_BOUNDED_HISTORY": True, "HMM_MAX_HISTORY": 900})
+        self.assertEqual(det.max_history(), 900)
+        self.assertIsNone(self._detector(None).max_history())
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class FuseTests(unittest.TestCase):
+    def _state(self, probs, ts=1.0):
+        p = np.asarray(probs, dtype=float)
+        top = np.sort(p)[::-1]
+        return hrd.RegimeState(
+            regime=hrd.Regime(int(np.argmax(p))),
+            probabilities=list(probs),
+            confidence=float(top[0] - top[1]),
+            peak_probability=float(top[0]),
+            bias_signal=float(p[2] - p[0]),
+            last_update_ts=ts,
+        )
+
+    def test_identical_states_fuse_to_same(self):
+        state = self._state([0.1, 0.2, 0.7])
+        for weight in (None, 0.3):
+            fused = state.fuse(state, weight)
+            self.assertEqual(fused.regime, state.regime)
+            np.testing.assert_allclose(fused.probabilities, state.probabilities)
+            self.assertAlmostEqual(fused.confidence, state.confidence)
+            self.assertAlmostEqual(fused.bias_signal, state.bias_signal)
+
+    def test_heavier_state_dominates(self):
+        bull = self._state([0.1, 0.2, 0.7], ts=5.0)
+        bear = self._state([0.6, 0.3, 0.1], ts=9.0)
+        fused = bull.fuse(bear, 0.8)
+        self.assertEqual(fused.regime, hrd.Regime.BULLISH)
+        self.assertGreater(fused.bias_signal, 0.0)
+        self.assertEqual(fused.last_update_ts, 9.0)
+        self.assertEqual(bull.fuse(bear, 0.2).regime, hrd.Regime.BEARISH)
+
+        # Confidence weighting: bull (0.5) outweighs bear (0.3).
+        self.assertEqual(bull.fuse(bear).regime, hrd.Regime.BULLISH)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+@unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+class PosteriorLabelsTests(unittest.TestCase):
+    def test_map_labels_differ_from_viterbi(self):
+        # Uninformative emissions, so only the chain matters. Paths over two
+        # bars: (0,1)=.2 (0,2)=.2 (1,1)=.32 (2,2)=.28. Viterbi takes (1,1);
+        # the per-bar marginals are t0: state 0 (.4), t1: state 1 (.52).
+        model = hrd.GaussianHMM(n_components=3, covariance_type="diag")
+        model.n_features = 4
+        model.startprob_ = np.array([0.4, 0.32, 0.28])
+        model.transmat_ = np.array([[0.0, 0.5, 0.5], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])
+        model.means_ = np.zeros((3, 4))
+        model.covars_ = np.ones((3, 4))
+        det = _make_detector()
+        det.model = model
+        det._state_label_map = {0: hrd.Regime.BEARISH, 1: hrd.Regime.RANGING, 2: hrd.Regime.BULLISH}
+        det._trained = True
+
+        rows = np.zeros((2, 4))
+        self.assertEqual(det._posterior_argmax(rows).tolist(), [0, 1])
+        self.assertEqual(model.predict(rows).tolist(), [1, 1])
+
+    def test_untrained_returns_none(self):
+        closes, volumes = _series()
+        self.assertIsNone(_make_detector().posterior_labels(closes, volumes))
+
+    def test_timestamps_align_past_warmup_drop(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        det.model.score_samples = _fixed_posterior([0.1, 0.1, 0.8])
+        closes, volumes = _series()
+        timestamps = [1_700_000_000 + 300 * i for i in range(len(closes))]
+        warmup = det.extractor.rsi_period  # rows RSI leaves NaN
+
+        pairs = det.posterior_labels(closes, volumes, timestamps=timestamps)
+        self.assertEqual(len(pairs), len(closes) - warmup)
+        self.assertEqual(pairs[0], (timestamps[warmup], hrd.Regime.BULLISH))
+        self.assertEqual(pairs[-1][0], timestamps[-1])
+        with self.assertRaises(ValueError):
+            det.posterior_labels(closes, volumes, timestamps=timestamps[1:])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class FeatureClampTests(unittest.TestCase):
+    def test_extreme_spread_and_volume_clamped(self):
+        closes, volumes = _series(n=200)
+        closes[150:] *= 3.0  # data glitch: price triples
+        volumes[180] *= 500.0
+        raw = hrd.FeatureExtractor().extract(closes, volumes)
+        fx = hrd.FeatureExtractor(ema_spread_clamp=0.05, volume_ratio_clamp=[0.0, 5.0])
+        obs = fx.extract(closes, volumes)
+
+        self.assertGreater(raw[:, 1].max(), 0.05)
+        self.assertGreater(raw[:, 3].max(), 5.0)
+        self.assertAlmostEqual(obs[:, 1].max(), 0.05)
+        self.assertAlmostEqual(obs[:, 3].max(), 5.0)
+        inside = (np.abs(raw[:, 1]) < 0.05) & (raw[:, 3] < 5.0)
+        np.testing.assert_array_equal(obs[inside], raw[inside])
+
+    def test_defaults_unbounded_and_invalid_rejected(self):
+        fx = hrd.FeatureExtractor()
+        self.assertIsNone(fx.config()["ema_spread_clamp"])
+        with self.assertRaises(ValueError):
+            fx.set_volume_ratio_clamp([2.0, 1.0])
+        det = _make_detector({"EMA_SPREAD_CLAMP": [-0.02, 0.03]})
+        self.assertEqual(det.extractor.config()["ema_spread_clamp"], [-0.02, 0.03])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class HealthTests(unittest.TestCase):
+    def _healthy(self, spread=3.0):
+        det = _install_fake_model(_make_detector(), **_gaussian_params(spread=spread))
+        det.training_depth = 4000
+        det._last_train_ts = hrd.time.time()
+        return det
+
+    def test_healthy_detector(self):
+        det = self._healthy()
+        self.assertEqual(det.health_reasons(), [])
+        self.assertTrue(det.health())
+
+    def test_each_failing_condition(self):
+        self.assertEqual(_make_detector().health_reasons(), ["untrained"])
+
+        det = self._healthy()
+        det._last_train_ts -= det.cfg["HMM_RETRAIN_INTERVAL_SEC"] + 1.0
+        self.assertEqual(det.health_reasons(), ["stale"])
+
+        det = self._healthy()
+        det.training_depth = 600
+        self.assertEqual(det.health_reasons(), ["shallow_training:shallow"])
+        self.assertFalse(det.health())
+
+        det = self._healthy(spread=0.0)
+        self.assertEqual(
+            det.health_reasons(),
+            ["low_separation:bearish_ranging", "low_separation:ranging_bullish"],
+        )
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class AdaptiveWindowTests(unittest.TestCase):
+    def _detector(self, adaptive=True):
+        params = _gaussian_params()
+        # Bearish reverses fast (dwell 4), ranging persists (dwell 50).
+        params["transmat_"] = np.array([
+            [0.75, 0.20, 0.05],
+            [0.01, 0.98, 0.01],
+            [0.05, 0.05, 0.90],
+        ])
+        return _install_fake_model(
+            _make_detector({
+                "HMM_ADAPTIVE_WINDOW": adaptive,
+                "HMM_ADAPTIVE_WINDOW_MIN": 8,
+                "HMM_ADAPTIVE_WINDOW_MAX": 100,
+            }),
+            **params,
+        )
+
+    def test_window_shrinks_when_short_dwell_regime_dominates(self):
+        det = self._detector()
+        closes, volumes = _series()
+        det.model.score_samples = _fixed_posterior([0.1, 0.8, 0.1])
+        self.assertEqual(det.update(closes, volumes).regime, hrd.Regime.RANGING)
+        self.assertEqual(det.effective_inference_window(), 50)
+
+        det.model.score_samples = _fixed_posterior([0.8, 0.1, 0.1])
+        self.assertEqual(det.update(closes, volumes).observation_count, 50)
+        self.assertEqual(det.effective_inference_window(), 8)
+        self.assertEqual(det.update(closes, volumes).observation_count, 8)
+
+    def test_fixed_by_default(self):
+        det = self._detector(adaptive=False)
+        det.state = hrd.RegimeState(regime=hrd.Regime.BEARISH)
+        self.assertEqual(det.effective_inference_window(), 50)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class TemperatureTests(unittest.TestCase):
+    def test_higher_temperature_flattens_without_moving_argmax(self):
+        params = _gaussian_params()
+        det = _install_fake_model(
+            _make_detector(), score_samples=_forward_backward_score_samples(params), **params
+        )
+        rows = np.zeros((5, 4))
+        rows[:, 1] = 0.8
+        peaks = []
+        for temperature in (1.0, 2.0, 4.0):
+            det.cfg["HMM_TEMPERATURE"] = temperature
+            last = det._posteriors(rows)[-1]
+            self.assertEqual(int(np.argmax(last)), 2)
+            peaks.append(float(last.max()))
+        self.assertGreater(peaks[0], peaks[1])
+        self.assertGreater(peaks[1], peaks[2])
+
+    def test_larger_emission_floor_is_less_peaked(self):
+        params = _gaussian_params(var=0.05)
+        det = _install_fake_model(
+            _make_detector(), score_samples=_forward_backward_score_samples(params), **params
+        )
+        rows = np.zeros((5, 4))
+        rows[:, 1] = 1.0  # squarely on the bullish mean
+        peaks = []
+        for floor in (0.0, 1e-3, 1e-1):
+            det.cfg["HMM_EMISSION_FLOOR"] = floor
+            last = det._posteriors(rows)[-1]
+            self.assertEqual(int(np.argmax(last)), 2)
+            peaks.append(float(last.max()))
+        self.assertGreater(peaks[0], peaks[1])
+        self.assertGreater(peaks[1], peaks[2])
+
+    def test_non_positive_temperature_rejected(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_TEMPERATURE": 0.0}), **_gaussian_params()
+        )
+        with self.assertRaises(ValueError):
+            det._posteriors(np.zeros((3, 4)))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class AsOfTimestampTests(unittest.TestCase):
+    def test_supplied_timestamp_stamps_state(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        det.model.score_samples = _fixed_posterior([0.1, 0.2, 0.7])
+        closes, volumes = _series()
+        self.assertEqual(det.update(closes, volumes, as_of_ts=1_000_000.0).last_update_ts, 1_000_000.0)
+        gated = hrd.GatedDetector(det)
+        self.assertEqual(gated.update(closes, volumes, as_of_ts=1_000_300.0).last_update_ts, 1_000_300.0)
+        self.assertGreater(det.update(closes, volumes).last_update_ts, 1_000_300.0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class StalledFeedTests(unittest.TestCase):
+    def _detector(self, span):
+        det = _install_fake_model(
+            _make_detector({"HMM_STALL_DECAY_UPDATES": span}), **_gaussian_params()
+        )
+        det.model.score_samples = _fixed_posterior([0.1, 0.1, 0.8])
+        return det
+
+    def test_repeated_tail_decays_confidence(self):
+        det = self._detector(4)
+        closes, volumes = _series()
+        fresh = det.update(closes, volumes).confidence
+        decayed = [det.update(closes, volumes).confidence for _ in range(4)]
+        self.assertEqual(det.stalled_updates(), 4)
+        self.assertAlmostEqual(decayed[0], fresh * 0.75, places=3)
+        self.assertTrue(all(a > b for a, b in zip(decayed, decayed[1:])))
+        self.assertEqual(decayed[-1], 0.0)
+
+    def test_new_bars_keep_confidence_fresh(self):
+        det = self._detector(4)
+        closes, volumes = _series(n=200)
+        confidences = [det.update(closes[:n], volumes[:n]).confidence for n in range(160, 165)]
+        self.assertEqual(det.stalled_updates(), 0)
+        self.assertEqual(len(set(confidences)), 1)
+
+    def test_off_by_default(self):
+        det = self._detector(0)
+        closes, volumes = _series()
+        first = det.update(closes, volumes).confidence
+        self.assertEqual(det.update(closes, volumes).confidence, first)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RunningStandardizerTests(unittest.TestCase):
+    def test_matches_batch_zscore_on_static_series(self):
+        rows = hrd.FeatureExtractor().extract(*_series(n=400))
+        online = hrd.RunningStandardizer(rows.shape[1])
+        emitted = np.array([online.push(row) for row in rows])
+
+        np.testing.assert_allclose(online.mean, rows.mean(axis=0), atol=1e-12)
+        np.testing.assert_allclose(online.std(), rows.std(axis=0), rtol=1e-9)
+        batch = (rows - rows.mean(axis=0)) / rows.std(axis=0)
+        np.testing.assert_allclose(emitted[-1], batch[-1], rtol=1e-9, atol=1e-12)
+        np.testing.assert_allclose(online.transform(rows[-50:]), batch[-50:], rtol=1e-9, atol=1e-12)
+
+    def test_passthrough_and_width_check(self):
+        online = hrd.RunningStandardizer(4, standardize=False)
+        row = np.array([1.0, 2.0, 3.0, 4.0])
+        np.testing.assert_array_equal(online.push(row), row)
+        self.assertEqual(online.count, 1)
+        with self.assertRaises(hrd.FeatureCountMismatch):
+            online.update([1.0, 2.0])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ReadyForInferenceTests(unittest.TestCase):
+    def test_trained_flag_without_model_is_not_ready(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        det.model = None
+        self.assertTrue(det._trained)
+        self.assertFalse(det.is_ready_for_inference(10_000))
+
+    def test_ready_only_with_enough_history(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        self.assertTrue(det.is_ready_for_inference(det.min_history()))
+        self.assertFalse(det.is_ready_for_inference(det.min_history() - 1))
+        self.assertFalse(_make_detector().is_ready_for_inference(10_000))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class TransitionInertiaTests(unittest.TestCase):
+    def setUp(self):
+        self.OLD = np.array([[0.96, 0.03, 0.01], [0.02, 0.96, 0.02], [0.01, 0.03, 0.96]])
+        self.NEW = np.array([[0.60, 0.30, 0.10], [0.20, 0.60, 0.20], [0.10, 0.30, 0.60]])
+
+    def _retrain(self, inertia, new_label_map):
+        params = _gaussian_params()
+        params["transmat_"] = self.OLD.copy()
+        det = _install_fake_model(_make_detector({"HMM_TRANSITION_INERTIA": inertia}), **params)
+        new_model = types.SimpleNamespace(transmat_=self.NEW.copy())
+        det._blend_transition_inertia(new_model, new_label_map)
+        return np.asarray(new_model.transmat_)
+
+    def test_high_inertia_stays_near_previous(self):
+        identity = {0: hrd.Regime.BEARISH, 1: hrd.Regime.RANGING, 2: hrd.Regime.BULLISH}
+        blended = self._retrain(0.9, identity)
+        np.testing.assert_allclose(blended.sum(axis=1), 1.0)
+        self.assertLess(np.abs(blended - self.OLD).max(), 0.05)
+        np.testing.assert_allclose(self._retrain(0.0, identity), self.NEW)
+
+    def test_alignment_follows_labels_not_raw_order(self):
+        # New fit found the same regimes in reversed raw order.
+        reversed_map = {0: hrd.Regime.BULLISH, 1: hrd.Regime.RANGING, 2: hrd.Regime.BEARISH}
+        blended = self._retrain(1.0, reversed_map)
+        np.testing.assert_allclose(blended, self.OLD[::-1, ::-1])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ReshapeProbabilitiesTests(unittest.TestCase):
+    PROBS = [0.2, 0.3, 0.5]
+
+    def test_unit_sharpness_is_noop(self):
+        np.testing.assert_allclose(hrd.reshape_probabilities(self.PROBS, 1.0), self.PROBS)
+
+    def test_high_sharpness_approaches_one_hot(self):
+        out = hrd.reshape_probabilities(self.PROBS, 50.0)
+        self.assertAlmostEqual(sum(out), 1.0)
+        self.assertGreater(out[2], 0.999)
+
+    def test_low_sharpness_approaches_uniform(self):
+        out = hrd.reshape_probabilities(self.PROBS, 0.01)
+        np.testing.assert_allclose(out, [1.0 / 3.0] * 3, atol=0.01)
+        self.assertEqual(hrd.reshape_probabilities(self.PROBS, 0.0), [1.0 / 3.0] * 3)
+
+    def test_zero_entries_stay_zero_and_bad_sharpness_rejected(self):
+        self.assertEqual(hrd.reshape_probabilities([0.0, 0.4, 0.6], 2.0)[0], 0.0)
+        with self.assertRaises(ValueError):
+            hrd.reshape_probabilities(self.PROBS, -1.0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class CorruptedPosteriorTests(unittest.TestCase):
+    def test_wrong_length_posterior_yields_valid_state(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        det.model.score_samples = lambda obs: (0.0, np.full((len(obs), 2), np.nan))
+        with self.assertLogs(hrd.logger, level="WARNING"):
+            state = det.update(*_series())
+        self.assertEqual(len(state.probabilities), 3)
+        self.assertTrue(state.is_normalized())
+        np.testing.assert_allclose(state.probabilities, [1.0 / 3.0] * 3)
+
+    def test_remap_tolerates_short_vector(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        np.testing.assert_allclose(det._remap_probs(np.array([0.4, 0.6])), [0.4, 0.6, 0.0])
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ReplayTests(unittest.TestCase):
+    def _detector(self):
+        params = _gaussian_params(spread=0.02, var=0.01)
+        return _install_fake_model(
+            _make_detector({"HMM_INFERENCE_WINDOW": 20}),
+            score_samples=_forward_backward_score_samples(params),
+            **params,
+        )
+
+    def test_matches_manual_per_bar_composition(self):
+        det = self._detector()
+        closes, volumes = _series(n=80)
+        decisions = det.replay(closes, volumes, confidence_threshold=0.1, step=3)
+
+        manual = det.fork()
+        expected = [
+            (i, hrd.compute_grid_bias(manual.update(closes[:i + 1], volumes[:i + 1]), 0.1))
+            for i in range(det.min_history() - 1, len(closes), 3)
+        ]
+        self.assertEqual(decisions, expected)
+        self.assertEqual(det.state, hrd.RegimeState())
+
+    def test_timestamps_key_the_decisions(self):
+        det = self._detector()
+        closes, volumes = _series(n=80)
+        timestamps = [1_700_000_000 + 60 * i for i in range(len(closes))]
+        decisions = det.replay(closes, volumes, timestamps=timestamps)
+        self.assertEqual(decisions[0][0], timestamps[det.min_history() - 1])
+        self.assertEqual(decisions[-1][0], timestamps[-1])
+        with self.assertRaises(ValueError):
+            det.replay(closes, volumes, step=0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class MinDwellTests(unittest.TestCase):
+    BULL = [0.1, 0.1, 0.8]
+    BEAR = [0.8, 0.1, 0.1]
+
+    def _emitted(self, min_dwell, sequence):
+        det = _install_fake_model(
+            _make_detector({"HMM_MIN_DWELL_BARS": min_dwell}), **_gaussian_params()
+        )
+        closes, volumes = _series()
+        out = []
+        for probs in sequence:
+            det.model.score_samples = _fixed_posterior(probs)
+            out.append(det.update(closes, volumes))
+        return out
+
+    def test_one_bar_blip_suppressed(self):
+        states = self._emitted(3, [self.BULL, self.BULL, self.BEAR, self.BULL])
+        self.assertTrue(all(s.regime == hrd.Regime.BULLISH for s in states))
+        # The true probabilities are still reported for the held bar.
+        np.testing.assert_allclose(states[2].probabilities, self.BEAR)
+
+    def test_first_emission_not_held(self):
+        R = hrd.Regime
+        # The startup RANGING state doesn't hold the first emission, but the
+        # first emitted regime counts as entered, so a blip out of it is held.
+        states = self._emitted(3, [self.BEAR, self.BULL, self.BEAR])
+        self.assertEqual([s.regime for s in states], [R.BEARISH] * 3)
+
+    def test_change_allowed_after_dwell_and_default_unconstrained(self):
+        R = hrd.Regime
+        held = self._emitted(3, [self.BULL] * 3 + [self.BEAR] * 3 + [self.BULL])
+        self.assertEqual([s.regime for s in held[2:5]], [R.BULLISH, R.BEARISH, R.BEARISH])
+        self.assertEqual(held[-1].regime, R.BULLISH)
+        free = self._emitted(1, [self.BULL, self.BEAR, self.BULL])
+        self.assertEqual([s.regime for s in free], [R.BULLISH, R.BEARISH, R.BULLISH])
+
+    def test_held_label_not_marked_neutralized(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_MIN_DWELL_BARS": 3, "HMM_NEUTRAL_BELOW_THRESHOLD": True}),
+            **_gaussian_params(),
+        )
+        closes, volumes = _series()
+        for probs in ([self.BULL] * 3 + [self.BEAR, [0.3, 0.3, 0.4]]):
+            det.model.score_samples = _fixed_posterior(probs)
+            state = det.update(closes, volumes)
+        self.assertEqual(state.regime, hrd.Regime.BEARISH)
+        self.assertFalse(state.neutralized)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class TrainingDepthWindowCapTests(unittest.TestCase):
+    def _detector(self, cap):
+        det = _install_fake_model(
+            _make_detector({"HMM_INFERENCE_WINDOW": 80, "HMM_CAP_WINDOW_TO_TRAINING": cap}),
+            **_gaussian_params(),
+        )
+        det.model.score_samples = _fixed_posterior([0.1, 0.2, 0.7])
+        det.training_depth = 30
+        return det
+
+    def test_window_capped_at_training_depth(self):
+        det = self._detector(True)
+        self.assertEqual(det.effective_inference_window(), 30)
+        state = det.update(*_series())
+        self.assertEqual(state.observation_count, 30)
+        self.assertTrue(state.window_capped)
+
+    def test_uncapped_by_default(self):
+        det = self._detector(False)
+        state = det.update(*_series())
+        self.assertEqual(state.observation_count, 80)
+        self.assertFalse(state.window_capped)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class AverageDirectionTests(unittest.TestCase):
+    def _detector(self, score_samples):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        det.model.score_samples = score_samples
+        return det
+
+    def test_consistently_bullish_window_near_plus_one(self):
+        det = self._detector(_fixed_posterior([0.02, 0.03, 0.95]))
+        self.assertAlmostEqual(det.average_direction(*_series(), window=20), 0.93)
+
+    def test_oscillating_window_near_zero(self):
+        def alternating(obs):
+            rows = np.tile([[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]], (len(obs), 1))[: len(obs)]
+            return 0.0, rows
+
+        det = self._detector(alternating)
+        self.assertAlmostEqual(det.average_direction(*_series(), window=20), 0.0)
+        with self.assertRaises(ValueError):
+            det.average_direction(*_series(), window=0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class SummaryLineTests(unittest.TestCase):
+    def test_known_state_tokens(self):
+        det = _make_detector()
+        det.training_depth = 3200
+        det.state = hrd.RegimeState(
+            regime=hrd.Regime.BULLISH,
+            confidence=0.4213,
+            bias_signal=0.18,
+            quality_tier="deep",
+            last_update_ts=1000.0,
+        )
+        line = det.summary_line(now=1012.3)
+        self.assertEqual(line, "regime=BULLISH conf=0.42 bias=0.18 tier=deep depth=3200 age=12.3s")
+
+    def test_never_updated_age(self):
+        self.assertTrue(_make_detector().summary_line().endswith("age=na"))
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class PartialWindowConfidenceTests(unittest.TestCase):
+    def _detector(self, scale=True):
+        det = _install_fake_model(
+            _make_detector({"HMM_INFERENCE_WINDOW": 50, "HMM_SCALE_CONFIDENCE_BY_WINDOW": scale}),
+            **_gaussian_params(),
+        )
+        det.model.score_samples = _fixed_posterior([0.1, 0.1, 0.8])
+        return det
+
+    def test_half_window_halves_confidence(self):
+        det = self._detector()
+        closes, volumes = _series()
+        full = det.update(closes, volumes)
+        self.assertEqual(full.observation_count, 50)
+        self.assertAlmostEqual(full.confidence, 0.7)
+
+        n = det.extractor.rsi_period + 25  # 25 rows survive warmup
+        half = self._detector().update(closes[:n], volumes[:n])
+        self.assertEqual(half.observation_count, 25)
+        self.assertAlmostEqual(half.confidence, 0.35)
+
+    def test_off_by_default(self):
+        closes, volumes = _series()
+        n = hrd.FeatureExtractor().rsi_period + 25
+        self.assertAlmostEqual(self._detector(False).update(closes[:n], volumes[:n]).confidence, 0.7)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class UpdateFastPathTests(unittest.TestCase):
+    def test_repeated_input_served_from_cache(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_UPDATE_FAST_PATH": True}), **_gaussian_params()
+        )
+        calls = []
+
+        def score_samples(obs):
+            calls.append(len(obs))
+            return 0.0, np.tile([0.1, 0.1, 0.8], (len(obs), 1))
+
+        det.model.score_samples = score_samples
+        closes, volumes = _series(n=200)
+        first = det.update(closes[:180], volumes[:180])
+        self.assertIs(det.update(closes[:180], volumes[:180]), first)
+        self.assertEqual(det._fast_path_hits, 1)
+        self.assertEqual(len(calls), 1)
+
+        det.update(closes[:181], volumes[:181])
+        self.assertEqual(det._fast_path_hits, 1)
+        self.assertEqual(len(calls), 2)
+
+    def test_window_or_config_change_recomputes(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_UPDATE_FAST_PATH": True}), **_gaussian_params()
+        )
+        det.model.score_samples = _fixed_posterior([0.1, 0.1, 0.8])
+        closes, volumes = _series(n=200)
+        self.assertEqual(det.update(closes, volumes).observation_count, 50)
+
+        det.set_inference_window(30)
+        self.assertEqual(det.update(closes, volumes).observation_count, 30)
+        self.assertEqual(det._fast_path_hits, 0)
+
+        det.cfg["HMM_CONFIDENCE_THRESHOLD"] = 0.9
+        self.assertEqual(det.update(closes, volumes).bias_signal, 0.0)
+        self.assertEqual(det._fast_path_hits, 0)
+        det.update(closes, volumes)
+        self.assertEqual(det._fast_path_hits, 1)
+
+    def test_other_emitter_or_timestamp_invalidates_cache(self):
+        det = _install_fake_model(
+            _make_detector({"HMM_UPDATE_FAST_PATH": True}), **_gaussian_params(stay=0.5)
+        )
+        det.model.score_samples = _fixed_posterior([0.1, 0.1, 0.8])
+        closes, volumes = _series()
+        plain = list(det.update(closes, volumes).probabilities)
+        ahead = list(det.update_lookahead(closes, volumes, 1.0).probabilities)
+        self.assertNotEqual(ahead, plain)
+        np.testing.assert_allclose(det.update(closes, volumes).probabilities, plain)
+        self.assertEqual(det._fast_path_hits, 0)
+
+        self.assertEqual(det.update(closes, volumes, as_of_ts=100.0).last_update_ts, 100.0)
+        self.assertEqual(det.update(closes, volumes, as_of_ts=200.0).last_update_ts, 200.0)
+        self.assertEqual(det.update(closes, volumes, as_of_ts=200.0).last_update_ts, 200.0)
+        self.assertEqual(det._fast_path_hits, 1)
+
+    def test_off_by_default(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        det.model.score_samples = _fixed_posterior([0.1, 0.1, 0.8])
+        closes, volumes = _series()
+        det.update(closes, volumes)
+        det.update(closes, volumes)
+        self.assertEqual(det._fast_path_hits, 0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class PreviousRegimeFeatureTests(unittest.TestCase):
+    def test_column_carries_prior_bar_regime(self):
+        det = _make_detector({"HMM_PREV_REGIME_FEATURE": True})
+        labeler = _install_fake_model(_make_detector(), **_gaussian_params())
+        cycle = np.eye(3)
+
+        def score_samples(obs):
+            return 0.0, cycle[np.arange(len(obs)) % 3]
+
+        labeler.model.score_samples = score_samples
+        det._regime_labeler = labeler
+
+        obs = det.extractor.extract(*_series())
+        augmented = det._with_previous_regime(obs)
+        self.assertEqual(augmented.shape, (len(obs), 5))
+        self.assertEqual(det.n_features, 5)
+        self.assertEqual(det.feature_names()[-1], hrd.PREV_REGIME_FEATURE)
+        np.testing.assert_array_equal(augmented[:, :4], obs)
+        # Row 0 is neutral; row t holds the direction labeled at row t - 1.
+        expected = [0.0] + [float((t % 3) - 1) for t in range(len(obs) - 1)]
+        np.testing.assert_array_equal(augmented[:, 4], expected)
+
+    def test_disabled_leaves_rows_unchanged(self):
+        det = _make_detector()
+        obs = det.extractor.extract(*_series())
+        self.assertIs(det._with_previous_regime(obs), obs)
+        self.assertEqual(det.n_features, 4)
+
+    @unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+    def test_train_fits_augmented_model(self):
+        det = hrd.RegimeDetector(
+            {"HMM_PREV_REGIME_FEATURE": True, "HMM_MIN_TRAIN_SAMPLES": 100, "HMM_N_ITER": 20}
+        )
+        closes, volumes = _series(n=400)
+        self.assertTrue(det.train(closes, volumes))
+        self.assertEqual(np.asarray(det.model.means_).shape[1], 5)
+        self.assertIsNotNone(det._regime_labeler)
+        det.update(closes, volumes)
+        self.assertAlmostEqual(sum(det.state.probabilities), 1.0, places=6)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class CalibrationReportTests(unittest.TestCase):
+    def test_confident_calls_agree_more_often(self):
+        det = _install_fake_model(_make_detector(), **_gaussian_params())
+        n = 160
+        closes = 0.15 + 0.001 * (np.arange(n) % 2)  # zigzag: every bar reverses
+        volumes = np.full(n, 5000.0)
+        _, index = det.extractor.extract_with_index(closes, volumes)
+
+        # Even rows: confident and right about the next bar; odd rows: weak and wrong.
+        rows = []
+        for i, k in enumerate(index):
+            up = k + 1 < n and closes[k + 1] > closes[k]
+            if i % 2 == 0:
+                rows.append([0.05, 0.05, 0.9] if up else [0.9, 0.05, 0.05])
+            else:
+                rows.append([0.45, 0.35, 0.2] if up else [0.2, 0.35, 0.45])
+        det._filtered_posteriors = lambda obs_norm: np.asarray(rows)
+
+        report = det.calibration_report(closes, volumes, horizon=1)
+        self.assertEqual(report["scored"], len(index) - 1)
+        low, high = report["buckets"]["0.00-0.20"], report["buckets"]["0.80-1.00"]
+        self.assertGreater(low["count"], 0)
+        self.assertGreater(high["count"], 0)
+        self.assertGreater(high["agreement"], low["agreement"])
+        self.assertIsNone(report["buckets"]["0.40-0.60"]["agreement"])
+
+    def test_untrained_and_bad_arguments(self):
+        det = _make_detector()
+        closes, volumes = _series()
+        self.assertIsNone(det.calibration_report(closes, volumes))
+        with self.assertRaises(ValueError):
+            det.calibration_report(closes, volumes, horizon=0)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class OnlineTransitionTests(unittest.TestCase):
+    def _detector(self, enabled):
+        det = _install_fake_model(
+            _make_detector({"HMM_ONLINE_TRANSITIONS": enabled, "HMM_ONLINE_TRANSITIONS_RATE": 0.1}),
+            **_gaussian_params(stay=0.6),
+        )
+        det.model.score_samples = _fixed_posterior([0.05, 0.05, 0.9])
+        return det
+
+    def test_sticky_run_raises_diagonal(self):
+        det = self._detector(True)
+        means = det.model.means_.copy()
+        covars = det.model.covars_.copy()
+        closes, volumes = _series(n=200)
+        diagonal = [float(det.model.transmat_[2, 2])]
+        for n in range(180, 186):
+            det.update(closes[:n], volumes[:n])
+            diagonal.append(float(det.model.transmat_[2, 2]))
+        self.assertTrue(all(b > a for a, b in zip(diagonal, diagonal[1:])), diagonal)
+        np.testing.assert_allclose(det.model.transmat_.sum(axis=1), 1.0)
+        np.testing.assert_array_equal(det.model.means_, means)
+        np.testing.assert_array_equal(det.model.covars_, covars)
+
+    def test_disabled_keeps_transmat(self):
+        det = self._detector(False)
+        before = det.model.transmat_.copy()
+        closes, volumes = _series(n=200)
+        det.update(closes[:180], volumes[:180])
+        det.update(closes[:181], volumes[:181])
+        np.testing.assert_array_equal(det.model.transmat_, before)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class TrainBatchesTests(unittest.TestCase):
+    def test_features_extracted_per_batch(self):
+        det = _make_detector()
+        c1, v1 = _series(n=120, seed=1)
+        c2, v2 = _series(n=140, seed=2)
+        c2 = c2 * 1.5  # overnight gap between the two days
+        calls = []
+
+        def capture(rows, training_depth=None, lengths=None):
+            calls.append((np.asarray(rows), training_depth, lengths))
+            return True
+
+        det.train_features = capture
+        self.assertTrue(det.train_batches([(c1, v1), (c2, v2)]))
+        rows, depth, lengths = calls[0]
+
+        per_batch = [det.extractor.extract(c1, v1), det.extractor.extract(c2, v2)]
+        np.testing.assert_array_equal(rows, np.concatenate(per_batch))
+        self.assertEqual(lengths, [len(per_batch[0]), len(per_batch[1])])
+        self.assertEqual(depth, 260)
+
+        naive = det.extractor.extract(np.concatenate([c1, c2]), np.concatenate([v1, v2]))
+        self.assertFalse(rows.shape == naive.shape and np.allclose(rows, naive))
+
+    def test_lengths_must_cover_rows(self):
+        det = _make_detector()
+        rows = det.extractor.extract(*_series())
+        with self.assertRaises(ValueError):
+            det.train_features(rows, lengths=[len(rows) - 1])
+
+    @unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+    def test_trains_on_batches(self):
+        det = hrd.RegimeDetector({"HMM_MIN_TRAIN_SAMPLES": 100, "HMM_N_ITER": 20})
+        batches = [_series(n=200, seed=1), _series(n=200, seed=2)]
+        self.assertTrue(det.train_batches(batches))
+        self.assertEqual(det.training_depth, 400)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class CandidateEvaluationTests(unittest.TestCase):
+    def test_holdout_must_fit_series(self):
+        det = _make_detector({"HMM_CANDIDATE_HOLDOUT": 500})
+        with self.assertRaises(ValueError):
+            det.evaluate_candidate(*_series(n=300))
+        self.assertFalse(det.commit_candidate())
+
+    def _retrain_to(self, det, probs, depth):
+        def fake_train(candidate, closes, volumes):
+            _install_fake_model(candidate, score_samples=_fixed_posterior(probs))
+            candidate.training_depth = depth
+            return True
+
+        with mock.patch.object(hrd.RegimeDetector, "train", fake_train):
+            return det.begin_retrain(*_series())
+
+    def test_scores_only_held_out_rows(self):
+        scored = []
+
+        def score(obs):
+            scored.append(np.array(obs))
+            return 0.0
+
+        det = _install_fake_model(_make_detector({"HMM_CANDIDATE_HOLDOUT": 100}), score=score)
+        closes, volumes = _series(n=600)
+
+        def fake_train(candidate, closes, volumes):
+            _install_fake_model(candidate, score=score)
+            return True
+
+        with mock.patch.object(hrd.RegimeDetector, "train", fake_train):
+            report = det.evaluate_candidate(closes, volumes)
+        self.assertEqual(report["holdout_rows"], 100)
+        self.assertEqual(len(scored), 2)
+        expected = det.extractor.extract(closes, volumes)[-100:]
+        for obs in scored:
+            np.testing.assert_allclose(obs, expected, atol=1e-9)
+
+    def test_live_model_serves_until_commit(self):
+        det = _install_fake_model(
+            _make_detector(), score_samples=_fixed_posterior([0.1, 0.1, 0.8])
+        )
+        det.training_depth = 100
+        closes, volumes = _series(n=200)
+        self.assertEqual(det.update(closes[:180], volumes[:180]).regime, hrd.Regime.BULLISH)
+
+        self.assertTrue(self._retrain_to(det, [0.8, 0.1, 0.1], depth=900))
+        self.assertTrue(det.has_candidate())
+        self.assertEqual(det.update(closes[:181], volumes[:181]).regime, hrd.Regime.BULLISH)
+        self.assertEqual(det.training_depth, 100)
+
+        self.assertTrue(det.commit_candidate())
+        self.assertFalse(det.has_candidate())
+        self.assertEqual(det.training_depth, 900)
+        self.assertEqual(det.update(closes[:182], volumes[:182]).regime, hrd.Regime.BEARISH)
+
+    def test_rollback_discards_candidate(self):
+        det = _install_fake_model(
+            _make_detector(), score_samples=_fixed_posterior([0.1, 0.1, 0.8])
+        )
+        live_model = det.model
+        self.assertTrue(self._retrain_to(det, [0.8, 0.1, 0.1], depth=900))
+        self.assertTrue(det.rollback_candidate())
+        self.assertFalse(det.rollback_candidate())
+        self.assertFalse(det.commit_candidate())
+        self.assertIs(det.model, live_model)
+
+    @unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+    def test_fresh_candidate_beats_stale_model(self):
+        cfg = {"HMM_MIN_TRAIN_SAMPLES": 100, "HMM_N_ITER": 30, "HMM_CANDIDATE_HOLDOUT": 150}
+        det = hrd.RegimeDetector(cfg)
+        rng = np.random.RandomState(3)
+        stale_closes = 0.15 * np.cumprod(1.0 + 0.0005 * rng.randn(400))
+        stale_volumes = np.abs(100.0 * rng.randn(400)) + 5000.0
+        self.assertTrue(det.train(stale_closes, stale_volumes))
+        live_model = det.model
+
+        closes, volumes = _series(n=600, seed=11)
+        closes = closes * np.cumprod(1.0 + 0.01 * rng.randn(600))
+        report = det.evaluate_candidate(closes, volumes)
+        self.assertIs(det.model, live_model)
+        self.assertEqual(report["holdout_rows"], 150)
+        self.assertGreaterEqual(
+            report["candidate_log_likelihood"], report["current_log_likelihood"]
+        )
+        self.assertTrue(report["recommend"])
+        self.assertTrue(det.commit_candidate())
+        self.assertIsNot(det.model, live_model)
+        self.assertEqual(det.training_depth, 450)
+
+
+class _RecordingHMM:
+    # GaussianHMM stand-in that records what it was fitted on.
+    fitted = []
+
+    def __init__(self, n_components=3, **kwargs):
+        self.n_components = n_components
+
+    def fit(self, obs, lengths=None):
+        _RecordingHMM.fitted.append((len(obs), lengths))
+        params = _gaussian_params()
+        self.startprob_ = params["startprob_"]
+        self.transmat_ = params["transmat_"]
+        self.means_ = np.zeros((self.n_components, obs.shape[1]))
+        self.means_[:, 1] = [-1.0, 0.0, 1.0]
+        self.covars_ = np.ones((self.n_components, obs.shape[1]))
+        return self
+
+    def predict_proba(self, obs, lengths=None):
+        return np.full((len(obs), self.n_components), 1.0 / self.n_components)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class TrainSubsampleTests(unittest.TestCase):
+    def setUp(self):
+        _RecordingHMM.fitted = []
+
+    def test_fits_every_kth_row_but_keeps_depth(self):
+        det = _make_detector({"HMM_TRAIN_SUBSAMPLE": 4, "HMM_MIN_TRAIN_SAMPLES": 100})
+        closes, volumes = _series(n=400)
+        rows = len(det.extractor.extract(closes, volumes))
+        with mock.patch.object(hrd, "GaussianHMM", _RecordingHMM):
+            self.assertTrue(det.train(closes, volumes))
+        self.assertEqual(_RecordingHMM.fitted[0][0], len(range(0, rows, 4)))
+        self.assertEqual(det.training_depth, 400)
+
+    def test_subsamples_each_batch(self):
+        det = _make_detector({"HMM_TRAIN_SUBSAMPLE": 3, "HMM_MIN_TRAIN_SAMPLES": 100})
+        rows = det.extractor.extract(*_series(n=300))
+        with mock.patch.object(hrd, "GaussianHMM", _RecordingHMM):
+            self.assertTrue(det.train_features(rows, lengths=[100, len(rows) - 100]))
+        fitted, lengths = _RecordingHMM.fitted[0]
+        self.assertEqual(lengths, [34, len(range(0, len(rows) - 100, 3))])
+        self.assertEqual(fitted, sum(lengths))
+        self.assertEqual(det.training_depth, len(rows))
+
+    def test_rejects_bad_step(self):
+        det = _make_detector({"HMM_TRAIN_SUBSAMPLE": 0, "HMM_MIN_TRAIN_SAMPLES": 100})
+        with self.assertRaises(ValueError):
+            det.train(*_series(n=300))
+
+    @unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+    def test_means_close_to_full_fit(self):
+        closes, volumes = _series(n=1200)
+        base = {"HMM_MIN_TRAIN_SAMPLES": 100, "HMM_N_ITER": 30}
+        full = hrd.RegimeDetector(base)
+        thin = hrd.RegimeDetector({**base, "HMM_TRAIN_SUBSAMPLE": 2})
+        self.assertTrue(full.train(closes, volumes))
+        self.assertTrue(thin.train(closes, volumes))
+
+        def spread_means(det):
+            raw = det.model.means_[:, 1] * det._obs_std[1] + det._obs_mean[1]
+            return np.sort(raw)
+
+        scale = float(full._obs_std[1])
+        np.testing.assert_allclose(spread_means(thin), spread_means(full), atol=scale)
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class RollingHitRateTests(unittest.TestCase):
+    def _trending(self, n=260):
+        rng = np.random.RandomState(5)
+        closes = 0.15 * np.cumprod(1.0 + 0.002 + 0.001 * rng.randn(n))
+        volumes = np.abs(1000.0 * rng.randn(n)) + 5000.0
+        return closes, volumes
+
+    def _detector(self, probs=(0.05, 0.05, 0.9)):
+        return _install_fake_model(
+            _make_detector(), score_samples=_fixed_posterior(list(probs)), **_gaussian_params()
+        )
+
+    def test_bullish_calls_on_uptrend(self):
+        det = self._detector()
+        closes, volumes = self._trending()
+        self.assertIsNone(det.rolling_hit_rate())
+        for n in range(180, 260):
+            det.update(closes[:n], volumes[:n])
+            det.update(closes[:n], volumes[:n])  # repeated tail doesn't resolve
+        self.assertEqual(len(det._hit_outcomes), 79)
+        self.assertGreater(det.rolling_hit_rate(), 0.8)
+
+    def test_ranging_not_tracked_and_snapshot_round_trip(self):
+        det = self._detector((0.1, 0.8, 0.1))
+        closes, volumes = self._trending()
+        for n in range(180, 190):
+            det.update(closes[:n], volumes[:n])
+        self.assertIsNone(det.rolling_hit_rate())
+
+        src = self._detector()
+        for n in range(180, 190):
+            src.update(closes[:n], volumes[:n])
+        dst = _make_detector()
+        hrd.restore_from_snapshot(dst, hrd.serialize_for_snapshot(src))
+        self.assertEqual(dst._hit_outcomes, src._hit_outcomes)
+        self.assertEqual(dst._pending_call, src._pending_call)
+        self.assertEqual(dst.rolling_hit_rate(), src.rolling_hit_rate())
+
+
+class _SlowEmHMM(_RecordingHMM):
+    # Runs its whole budget unless told to converge after `settle` iterations.
+    settle = None
+
+    def __init__(self, n_components=3, n_iter=10, tol=1e-2, **kwargs):
+        super().__init__(n_components)
+        self.n_iter = n_iter
+        self.tol = tol
+
+    def fit(self, obs, lengths=None):
+        super().fit(obs, lengths)
+        iterations = self.n_iter if self.settle is None else min(self.n_iter, self.settle)
+        gain = self.tol / 10.0 if self.settle is not None else self.tol * 5.0
+        self.monitor_ = types.SimpleNamespace(iter=iterations, history=[-100.0, -100.0 + gain])
+        return self
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class MaxIterTests(unittest.TestCase):
+    def _train(self, settle, **cfg):
+        _SlowEmHMM.settle = settle
+        det = _make_detector({"HMM_MIN_TRAIN_SAMPLES": 100, "HMM_N_ITER": 100, **cfg})
+        with mock.patch.object(hrd, "GaussianHMM", _SlowEmHMM):
+            self.assertTrue(det.train(*_series(n=300)))
+        return det
+
+    def test_slow_fit_stops_at_ceiling(self):
+        det = self._train(None, HMM_MAX_ITER=7)
+        self.assertEqual(det.model.n_iter, 7)
+        self.assertEqual(
+            det.fit_termination(), {"reason": "max_iter", "iterations": 7, "max_iter": 7}
+        )
+
+    def test_converged_fit_reports_tolerance(self):
+        det = self._train(3, HMM_MAX_ITER=50, HMM_TOL=1e-3)
+        self.assertEqual(det.model.tol, 1e-3)
+        self.assertEqual(det.fit_termination()["reason"], "converged")
+        self.assertEqual(det.fit_termination()["iterations"], 3)
+
+    def test_committed_candidate_reports_its_termination(self):
+        det = self._train(None, HMM_MAX_ITER=7)
+        _SlowEmHMM.settle = 3
+        with mock.patch.object(hrd, "GaussianHMM", _SlowEmHMM):
+            self.assertTrue(det.begin_retrain(*_series(n=300)))
+        self.assertEqual(det.fit_termination()["reason"], "max_iter")
+        self.assertTrue(det.commit_candidate())
+        self.assertEqual(
+            det.fit_termination(), {"reason": "converged", "iterations": 3, "max_iter": 7}
+        )
+
+    def test_default_ceiling_is_n_iter(self):
+        det = self._train(None)
+        self.assertEqual(det.fit_termination()["max_iter"], 100)
+        self.assertIsNone(_make_detector().fit_termination())
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+class ConfigReportTests(unittest.TestCase):
+    def test_unknown_keys_reported_as_ignored(self):
+        det = _make_detector({"HMM_INFERENCE_WINDOWS": 80, "HMM_N_ITER": 40, "HMM_TOL": 1e-3})
+        report = det.config_report()
+        self.assertEqual(report["ignored"], ["HMM_INFERENCE_WINDOWS"])
+        self.assertEqual(report["applied"], {"HMM_N_ITER": 40, "HMM_TOL": 1e-3})
+        self.assertEqual(report["effective"]["HMM_INFERENCE_WINDOW"], 50)
+        self.assertEqual(report["effective"]["HMM_N_ITER"], 40)
+
+    def test_defaults_only(self):
+        report = _make_detector().config_report()
+        self.assertEqual((report["applied"], report["ignored"]), ({}, []))
+        self.assertEqual(report["effective"], hrd.RegimeDetector.DEFAULT_CONFIG)
+
+
+_FIXTURES_DIR = os.path.join(os.path.dirname(os.path.abspath(__file__)), "fixtures")
+_GOLDEN_PATH = os.path.join(_FIXTURES_DIR, "hmm_golden.json")
+
+
+def _load_golden_builder():
+    spec = importlib.util.spec_from_file_location(
+        "make_hmm_golden", os.path.join(_FIXTURES_DIR, "make_hmm_golden.py")
+    )
+    module = importlib.util.module_from_spec(spec)
+    spec.loader.exec_module(module)
+    return module
+
+
+@unittest.skipIf(hrd is None, f"hmm_regime_detector import failed: {_IMPORT_ERROR}")
+@unittest.skipIf(hrd is not None and hrd.GaussianHMM is None, "hmmlearn not installed")
+class GoldenPipelineTests(unittest.TestCase):
+    """End-to-end numeric regression guard; regenerate via tests/fixtures/make_hmm_golden.py."""
+
+    def test_train_update_matches_golden_fixture(self):
+        self.assertTrue(
+            os.path.exists(_GOLDEN_PATH),
+            "golden fixture missing; run tests/fixtures/make_hmm_golden.py and commit it",
+        )
+        with open(_GOLDEN_PATH, "r", encoding="utf-8") as fh:
+            golden = json.load(fh)
+
+        det = hrd.RegimeDetector(golden["config"])
+        closes = np.asarray(golden["closes"], dtype=float)
+        volumes = np.asarray(golden["volumes"], dtype=float)
+        self.assertTrue(det.train(closes, volumes))
+        state = det.update(closes, volumes)
+
+        expected = golden["expected"]
+        self.assertEqual(int(state.regime), int(expected["regime"]))
+        self.assertEqual(len(state.probabilities), len(expected["probabilities"]))
+        for got, want in zip(state.probabilities, expected["probabilities"]):
+            self.assertAlmostEqual(got, want, places=6)
+        self.assertAlmostEqual(state.confidence, expected["confidence"], places=6)
+        self.assertAlmostEqual(state.bias_signal, expected["bias_signal"], places=6)
+
+    def test_training_is_deterministic_under_fixed_seed(self):
+        make_hmm_golden = _load_golden_builder()
+        closes, volumes = make_hmm_golden.build_series()
+        first = make_hmm_golden.run_pipeline(closes, volumes, make_hmm_golden.CONFIG)
+        second = make_hmm_golden.run_pipeline(closes, volumes, make_hmm_golden.CONFIG)
+        self.assertEqual(first, second)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,80 @@
+import contextlib
+import importlib.util
+import io
+import os
+import tempfile
+import unittest
+
+_ROOT = os.path.dirname(os.path.dirname(os.path.abspath(__file__)))
+_SAMPLE_CSV = os.path.join(_ROOT, "tests", "fixtures", "label_csv_sample.csv")
+
+try:
+    _spec = importlib.util.spec_from_file_location(
+        "label_csv", os.path.join(_ROOT, "tools", "label_csv.py")
+    )
+    label_csv = importlib.util.module_from_spec(_spec)
+    _spec.loader.exec_module(label_csv)
+except Exception as exc:  # pragma: no cover
+    label_csv = None
+    _IMPORT_ERROR = exc
+else:  # pragma: no cover
+    _IMPORT_ERROR = None
+
+
+def _write_csv(text):
+    fh = tempfile.NamedTemporaryFile("w", suffix=".csv", delete=False)
+    fh.write(text)
+    fh.close()
+    return fh.name
+
+
+@unittest.skipIf(label_csv is None, f"label_csv import failed: {_IMPORT_ERROR}")
+class LabelCsvSchemaTests(unittest.TestCase):
+    def test_bad_header_and_rows_raise_with_location(self):
+        cases = {
+            "ts,close,volume\n1,0.1,5\n": "expected header",
+            "timestamp,close,volume\n1,0.1\n": ":2: expected 3 fields",
+            "timestamp,close,volume\n1,0.1,5\n2,abc,5\n": ":3: close/volume must be numeric",
+            "timestamp,close,volume\n": "no data rows",
+        }
+        for text, message in cases.items():
+            path = _write_csv(text)
+            try:
+                with self.assertRaises(label_csv.CsvSchemaError) as ctx:
+                    label_csv.read_candles(path)
+                self.assertIn(message, str(ctx.exception))
+            finally:
+                os.unlink(path)
+
+    def test_main_reports_schema_error_exit_code(self):
+        path = _write_csv("open,high\n")
+        try:
+            with contextlib.redirect_stderr(io.StringIO()) as err:
+                self.assertEqual(label_csv.main([path]), 2)
+            self.assertIn("expected header", err.getvalue())
+        finally:
+            os.unlink(path)
+
+
+@unittest.skipIf(label_csv is None, f"label_csv import failed: {_IMPORT_ERROR}")
+@unittest.skipIf(
+    label_csv is not None and label_csv.hrd.GaussianHMM is None, "hmmlearn not installed"
+)
+class LabelCsvEndToEndTests(unittest.TestCase):
+    def test_sample_fixture_labels_every_row(self):
+        out = io.StringIO()
+        with contextlib.redirect_stdout(out):
+            code = label_csv.main([_SAMPLE_CSV, "--min-train-samples", "100", "--n-iter", "20"])
+        self.assertEqual(code, 0)
+
+        lines = out.getvalue().strip().splitlines()
+        self.assertEqual(lines[0], "timestamp,regime")
+        _, closes, _ = label_csv.read_candles(_SAMPLE_CSV)
+        self.assertEqual(len(lines) - 1, len(closes))
+        labels = {line.split(",")[1] for line in lines[1:]}
+        self.assertTrue(labels <= {"WARMUP", "BEARISH", "RANGING", "BULLISH"})
+        self.assertIn("WARMUP", labels)
+
+
+if __name__ == "__main__":
+    unittest.main()
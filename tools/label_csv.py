@@ -0,0 +1,103 @@
+#!/usr/bin/env python3
+"""Label a candle CSV with HMM regimes, offline.
+
+Reads ``timestamp,close,volume`` rows (oldest first), trains a RegimeDetector
+on the whole file and prints the Viterbi regime for each row. Rows consumed by
+indicator warmup (or dropped as non-finite) are labeled ``WARMUP``.
+
+Usage:
+  python tools/label_csv.py candles.csv [--min-train-samples N] [--n-iter N] [--seed N]
+"""
+import argparse
+import csv
+import os
+import sys
+
+sys.path.insert(0, os.path.dirname(os.path.dirname(os.path.abspath(__file__))))
+
+import hmm_regime_detector as hrd
+
+COLUMNS = ("timestamp", "close", "volume")
+
+
+class CsvSchemaError(ValueError):
+    """The input CSV does not match the timestamp,close,volume schema."""
+
+
+def read_candles(path):
+    """Return (timestamps, closes, volumes) parsed from `path`."""
+    timestamps, closes, volumes = [], [], []
+    with open(path, newline="") as fh:
+        reader = csv.reader(fh)
+        header = next(reader, None)
+        if header is None:
+            raise CsvSchemaError(f"{path}: empty file")
+        if tuple(h.strip().lower() for h in header) != COLUMNS:
+            raise CsvSchemaError(
+                f"{path}: expected header {','.join(COLUMNS)}, got {','.join(header)}"
+            )
+        for line_no, row in enumerate(reader, start=2):
+            if not row:
+                continue
+            if len(row) != len(COLUMNS):
+                raise CsvSchemaError(
+                    f"{path}:{line_no}: expected {len(COLUMNS)} fields, got {len(row)}"
+                )
+            try:
+                close = float(row[1])
+                volume = float(row[2])
+            except ValueError:
+                raise CsvSchemaError(f"{path}:{line_no}: close/volume must be numeric") from None
+            timestamps.append(row[0].strip())
+            closes.append(close)
+            volumes.append(volume)
+    if not closes:
+        raise CsvSchemaError(f"{path}: no data rows")
+    return timestamps, closes, volumes
+
+
+def label_rows(timestamps, closes, volumes, config=None):
+    """Train on the series and return [(timestamp, label)] for every row."""
+    detector = hrd.RegimeDetector(config)
+    if not detector.train(closes, volumes):
+        raise RuntimeError(
+            f"training failed on {len(closes)} rows "
+            f"(HMM_MIN_TRAIN_SAMPLES={detector.cfg['HMM_MIN_TRAIN_SAMPLES']})"
+        )
+    rows = list(range(len(closes)))
+    path = dict(detector.viterbi_regimes(closes, volumes, timestamps=rows) or [])
+    labels = [path[i].as_str() if i in path else "WARMUP" for i in rows]
+    return list(zip(timestamps, labels))
+
+
+def main(argv=None):
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("csv_path")
+    parser.add_argument("--min-train-samples", type=int, default=None)
+    parser.add_argument("--n-iter", type=int, default=None)
+    parser.add_argument("--seed", type=int, default=None)
+    args = parser.parse_args(argv)
+
+    config = {}
+    if args.min_train_samples is not None:
+        config["HMM_MIN_TRAIN_SAMPLES"] = args.min_train_samples
+    if args.n_iter is not None:
+        config["HMM_N_ITER"] = args.n_iter
+    if args.seed is not None:
+        config["HMM_RANDOM_SEED"] = args.seed
+
+    try:
+        timestamps, closes, volumes = read_candles(args.csv_path)
+        rows = label_rows(timestamps, closes, volumes, config)
+    except (CsvSchemaError, RuntimeError, OSError) as e:
+        print(f"error: {e}", file=sys.stderr)
+        return 2
+
+    writer = csv.writer(sys.stdout)
+    writer.writerow(("timestamp", "regime"))
+    writer.writerows(rows)
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())